@@ -0,0 +1,238 @@
+// Copyright (C) 2022  float3
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::error::Error;
+
+use serde_json::{Map, Value};
+
+use crate::{
+    batch::{translate_segments, Masked},
+    client::{Client, RequestOptions},
+    lang::{LanguageCode, TargetLanguage},
+    structured::{json_indent, write_json},
+};
+
+// translates the messages of a flutter arb file
+// `@key` metadata and other `@@` globals are kept, `@@locale` is set to the target language
+// icu placeholders like `{name}` and `{count, number}` are never sent, the variants of plural
+// and select arguments are translated one by one
+pub fn translate_arb(
+    client: &Client,
+    arb: &str,
+    source_language: LanguageCode,
+    target_language: TargetLanguage,
+    options: &RequestOptions,
+) -> Result<String, Box<dyn Error>> {
+    let Value::Object(map) = serde_json::from_str(arb)? else {
+        return Result::Err("an arb file must be a json object".into());
+    };
+    let messages: Vec<(String, Vec<Part>)> = map
+        .iter()
+        .filter(|(key, _)| !key.starts_with('@'))
+        .filter_map(|(key, value)| {
+            let mut parts = Vec::new();
+            parse_message(value.as_str()?, false, &mut parts);
+            Some((key.clone(), parts))
+        })
+        .collect();
+    let segments: Vec<&str> = messages
+        .iter()
+        .flat_map(|(_, parts)| parts)
+        .filter_map(|part| match part {
+            Part::Text(masked) if masked.has_text() => Some(masked.text()),
+            _ => None,
+        })
+        .collect();
+    let mut translations =
+        translate_segments(client, &segments, source_language, target_language, options)?
+            .into_iter();
+
+    let mut translated: Map<String, Value> = Map::new();
+    let locale = target_language.language_code().bcp_47().replace('-', "_");
+    if !map.contains_key("@@locale") {
+        translated.insert("@@locale".to_string(), Value::String(locale.clone()));
+    }
+    let mut messages = messages.into_iter().peekable();
+    for (key, value) in map {
+        let value = match messages.next_if(|(message, _)| *message == key) {
+            Some((_, parts)) => Value::String(render(&parts, &mut translations)),
+            None if key == "@@locale" => Value::String(locale.clone()),
+            None => value,
+        };
+        translated.insert(key, value);
+    }
+
+    let mut output = write_json(&Value::Object(translated), json_indent(arb), ": ");
+    if arb.ends_with('\n') {
+        output.push('\n');
+    }
+    Result::Ok(output)
+}
+
+// an icu message split at the syntax of its plural and select arguments
+enum Part {
+    Syntax(String),
+    Text(Masked),
+}
+
+// splits an icu message into parts, `#` inside plural variants is a placeholder too
+fn parse_message(message: &str, in_plural: bool, parts: &mut Vec<Part>) {
+    let mut masked = Masked::default();
+    let mut rest = message;
+    while let Some(start) = rest.find(['{', '#', '\'']) {
+        masked.push_text_masking_placeholders(&rest[..start], str::to_string);
+        rest = &rest[start..];
+        if rest.starts_with('\'') {
+            // a quoted section is literal text and kept as it is, `''` is an apostrophe
+            let length = quoted_length(rest, in_plural);
+            match length {
+                Some(2) if rest.starts_with("''") => masked.push_text("'"),
+                Some(length) => masked.push_placeholder(&rest[..length]),
+                None => masked.push_text("'"),
+            }
+            rest = &rest[length.unwrap_or(1)..];
+            continue;
+        }
+        if rest.starts_with('#') {
+            if in_plural {
+                masked.push_placeholder("#");
+            } else {
+                masked.push_text("#");
+            }
+            rest = &rest[1..];
+            continue;
+        }
+        let Some(end) = matching_brace(rest, in_plural) else {
+            masked.push_text("{");
+            rest = &rest[1..];
+            continue;
+        };
+        let argument = &rest[..end];
+        rest = &rest[end..];
+        let mut fields = argument[1..argument.len() - 1].splitn(3, ',');
+        let (_, kind, variants) = (fields.next(), fields.next(), fields.next());
+        let kind = kind.map(str::trim).unwrap_or_default();
+        let Some(variants) =
+            variants.filter(|_| matches!(kind, "plural" | "select" | "selectordinal"))
+        else {
+            masked.push_placeholder(argument);
+            continue;
+        };
+
+        if !masked.text().is_empty() {
+            parts.push(Part::Text(std::mem::take(&mut masked)));
+        }
+        let variants_start = argument.len() - 1 - variants.len();
+        parts.push(Part::Syntax(argument[..variants_start].to_string()));
+        let mut variants = variants;
+        while let Some(open) = variants.find('{') {
+            let Some(close) = matching_brace(&variants[open..], in_plural || kind != "select")
+            else {
+                break;
+            };
+            parts.push(Part::Syntax(variants[..=open].to_string()));
+            parse_message(
+                &variants[open + 1..open + close - 1],
+                in_plural || kind != "select",
+                parts,
+            );
+            parts.push(Part::Syntax("}".to_string()));
+            variants = &variants[open + close..];
+        }
+        parts.push(Part::Syntax(format!("{}}}", variants)));
+    }
+    masked.push_text_masking_placeholders(rest, str::to_string);
+    if !masked.text().is_empty() {
+        parts.push(Part::Text(masked));
+    }
+}
+
+// the length of the braces at the start of the text, with the braces nested in them
+fn matching_brace(text: &str, in_plural: bool) -> Option<usize> {
+    let mut depth = 0;
+    let mut offset = 0;
+    while let Some(c) = text[offset..].chars().next() {
+        match c {
+            '\'' => {
+                offset += quoted_length(&text[offset..], in_plural).unwrap_or(1);
+                continue;
+            }
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(offset + 1);
+                }
+            }
+            _ => {}
+        }
+        offset += c.len_utf8();
+    }
+    None
+}
+
+// the length of the quoted section or `''` at the start of the text, None for an apostrophe
+// that icu reads as it is because no syntax character follows it
+fn quoted_length(text: &str, in_plural: bool) -> Option<usize> {
+    let rest = text.strip_prefix('\'')?;
+    if rest.starts_with('\'') {
+        return Some(2);
+    }
+    if !(rest.starts_with(['{', '}', '|']) || in_plural && rest.starts_with('#')) {
+        return None;
+    }
+    // the section ends at the next single apostrophe, or with the message
+    let mut offset = 0;
+    while let Some(quote) = rest[offset..].find('\'') {
+        offset += quote + 1;
+        if !rest[offset..].starts_with('\'') {
+            return Some(offset + 1);
+        }
+        offset += 1;
+    }
+    Some(text.len())
+}
+
+// doubles the apostrophes icu would read as the start of a quoted section
+fn escape_apostrophes(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for (offset, c) in text.char_indices() {
+        escaped.push(c);
+        if c == '\'' {
+            let next = text[offset + 1..].chars().next();
+            if next.is_none_or(|next| "{}#|'".contains(next)) {
+                escaped.push('\'');
+            }
+        }
+    }
+    escaped
+}
+
+fn render(parts: &[Part], translations: &mut impl Iterator<Item = String>) -> String {
+    let mut message = String::new();
+    for part in parts {
+        match part {
+            Part::Syntax(syntax) => message.push_str(syntax),
+            Part::Text(masked) if masked.has_text() => {
+                let translation = translations.next().unwrap_or_default();
+                message.push_str(&masked.unmask(&escape_apostrophes(&translation)))
+            }
+            Part::Text(masked) => {
+                message.push_str(&masked.unmask(&escape_apostrophes(masked.text())))
+            }
+        }
+    }
+    message
+}
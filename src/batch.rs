@@ -0,0 +1,389 @@
+// Copyright (C) 2022  float3
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{collections::HashMap, error::Error};
+
+use crate::{
+    client::{Client, RequestOptions},
+    lang::{LanguageCode, TargetLanguage},
+    MAX_TEXT_LENGTH,
+};
+
+// translates many short texts with as few requests as possible, as used by the document formats
+// identical texts are only sent once, surrounding whitespace is kept as is and blank texts are not sent at all
+// single line texts are joined with newlines into requests of up to MAX_TEXT_LENGTH bytes
+pub fn translate_segments(
+    client: &Client,
+    segments: &[&str],
+    source_language: LanguageCode,
+    target_language: TargetLanguage,
+    options: &RequestOptions,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut unique: Vec<&str> = Vec::new();
+    let mut positions: HashMap<&str, usize> = HashMap::new();
+    for segment in segments {
+        let text = segment.trim();
+        if !text.is_empty() && !positions.contains_key(text) {
+            positions.insert(text, unique.len());
+            unique.push(text);
+        }
+    }
+    let batch = Batch {
+        client,
+        source_language,
+        target_language,
+        options,
+    };
+    let translations = batch.translate_unique(&unique)?;
+    Result::Ok(
+        segments
+            .iter()
+            .map(|segment| {
+                let text = segment.trim();
+                if text.is_empty() {
+                    return segment.to_string();
+                }
+                let (leading, trailing) = surrounding_whitespace(segment);
+                format!("{}{}{}", leading, translations[positions[text]], trailing)
+            })
+            .collect(),
+    )
+}
+
+struct Batch<'a> {
+    client: &'a Client,
+    source_language: LanguageCode,
+    target_language: TargetLanguage,
+    options: &'a RequestOptions,
+}
+
+impl Batch<'_> {
+    fn translate_unique(&self, texts: &[&str]) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut translations = vec![String::new(); texts.len()];
+        let mut pending: Vec<usize> = Vec::new();
+        let mut pending_length = 0;
+        for (index, text) in texts.iter().enumerate() {
+            if text.contains('\n') || text.len() > MAX_TEXT_LENGTH {
+                translations[index] = self.translate_long(text)?;
+                continue;
+            }
+            if !pending.is_empty() && pending_length + 1 + text.len() > MAX_TEXT_LENGTH {
+                self.flush(texts, &pending, &mut translations)?;
+                pending.clear();
+                pending_length = 0;
+            }
+            pending_length += text.len() + usize::from(!pending.is_empty());
+            pending.push(index);
+        }
+        if !pending.is_empty() {
+            self.flush(texts, &pending, &mut translations)?;
+        }
+        Result::Ok(translations)
+    }
+
+    fn flush(
+        &self,
+        texts: &[&str],
+        pending: &[usize],
+        translations: &mut [String],
+    ) -> Result<(), Box<dyn Error>> {
+        let joined = pending
+            .iter()
+            .map(|index| texts[*index])
+            .collect::<Vec<_>>()
+            .join("\n");
+        let translated = self.translate(&joined)?;
+        let lines: Vec<&str> = translated.split('\n').collect();
+        if lines.len() == pending.len() {
+            for (index, line) in pending.iter().zip(lines) {
+                translations[*index] = line.trim().to_string();
+            }
+            return Result::Ok(());
+        }
+        // google merged or split lines, so the batch can not be mapped back
+        for index in pending {
+            translations[*index] = self.translate(texts[*index])?;
+        }
+        Result::Ok(())
+    }
+
+    fn translate_long(&self, text: &str) -> Result<String, Box<dyn Error>> {
+        let mut translated = String::new();
+        for chunk in split_text(text, MAX_TEXT_LENGTH) {
+            if chunk.trim().is_empty() {
+                translated.push_str(chunk);
+                continue;
+            }
+            let (leading, trailing) = surrounding_whitespace(chunk);
+            translated.push_str(leading);
+            translated.push_str(&self.translate(chunk.trim())?);
+            translated.push_str(trailing);
+        }
+        Result::Ok(translated)
+    }
+
+    fn translate(&self, text: &str) -> Result<String, Box<dyn Error>> {
+        Result::Ok(
+            self.client
+                .translate_text_with(
+                    text,
+                    self.source_language,
+                    self.target_language,
+                    self.options,
+                )?
+                .trim()
+                .to_string(),
+        )
+    }
+}
+
+fn surrounding_whitespace(text: &str) -> (&str, &str) {
+    let start = text.len() - text.trim_start().len();
+    let end = text.trim_end().len();
+    if start >= end {
+        return (text, "");
+    }
+    (&text[..start], &text[end..])
+}
+
+// pieces of at most max bytes that concatenate back to the text
+// cuts after a paragraph, line or sentence if possible and never inside a word unless it is longer than max
+pub(crate) fn split_text(text: &str, max: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = text;
+    while rest.len() > max {
+        let mut limit = max;
+        while !rest.is_char_boundary(limit) {
+            limit -= 1;
+        }
+        let window = &rest[..limit];
+        let cut = ["\n\n", "\n", ". ", "? ", "! ", "。", " "]
+            .iter()
+            .find_map(|separator| {
+                window
+                    .rfind(separator)
+                    .map(|position| position + separator.len())
+                    .filter(|position| *position > 0)
+            })
+            .unwrap_or(limit);
+        chunks.push(&rest[..cut]);
+        rest = &rest[cut..];
+    }
+    if !rest.is_empty() {
+        chunks.push(rest);
+    }
+    chunks
+}
+
+// text whose placeholders are swapped for numbered tokens like {0} that google keeps as they are
+#[derive(Debug, Default)]
+pub(crate) struct Masked {
+    text: String,
+    placeholders: Vec<String>,
+    has_text: bool,
+}
+
+impl Masked {
+    pub(crate) fn push_text(&mut self, text: &str) {
+        self.has_text |= text.chars().any(char::is_alphabetic);
+        self.text.push_str(text);
+    }
+
+    pub(crate) fn push_placeholder(&mut self, placeholder: &str) {
+        self.text
+            .push_str(&format!("{{{}}}", self.placeholders.len()));
+        self.placeholders.push(placeholder.to_string());
+    }
+
+    // like mask_placeholders for a part of the text, `render` turns a placeholder into what
+    // unmask puts back, as the escaped form for markup
+    pub(crate) fn push_text_masking_placeholders(
+        &mut self,
+        text: &str,
+        render: impl Fn(&str) -> String,
+    ) {
+        let mut rest = text;
+        while !rest.is_empty() {
+            let start = rest.find(['{', '%', '$', '@']).unwrap_or(rest.len());
+            self.push_text(&rest[..start]);
+            rest = &rest[start..];
+            if rest.is_empty() {
+                break;
+            }
+            let length = placeholder_length(rest);
+            if length == 0 {
+                let c = rest.chars().next().map_or(1, char::len_utf8);
+                self.push_text(&rest[..c]);
+                rest = &rest[c..];
+            } else {
+                self.push_placeholder(&render(&rest[..length]));
+                rest = &rest[length..];
+            }
+        }
+    }
+
+    pub(crate) fn text(&self) -> &str {
+        &self.text
+    }
+
+    // whether there is anything to translate besides the placeholders
+    pub(crate) fn has_text(&self) -> bool {
+        self.has_text
+    }
+
+    // puts the placeholders back, tokens google dropped are appended at the end
+    pub(crate) fn unmask(&self, translation: &str) -> String {
+        let mut unmasked = String::with_capacity(translation.len());
+        let mut used = vec![false; self.placeholders.len()];
+        let mut rest = translation;
+        while let Some(start) = rest.find(['{', '｛']) {
+            unmasked.push_str(&rest[..start]);
+            let after = &rest[start..];
+            match parse_token(after).filter(|(index, _)| *index < self.placeholders.len()) {
+                Some((index, length)) => {
+                    unmasked.push_str(&self.placeholders[index]);
+                    used[index] = true;
+                    rest = &after[length..];
+                }
+                None => {
+                    let brace = after.chars().next().map_or(1, char::len_utf8);
+                    unmasked.push_str(&after[..brace]);
+                    rest = &after[brace..];
+                }
+            }
+        }
+        unmasked.push_str(rest);
+        for (placeholder, used) in self.placeholders.iter().zip(used) {
+            if !used {
+                if !unmasked.is_empty() && !unmasked.ends_with(char::is_whitespace) {
+                    unmasked.push(' ');
+                }
+                unmasked.push_str(placeholder);
+            }
+        }
+        unmasked
+    }
+}
+
+// `{0}` with the spaces and full width braces google sometimes adds, returns the index and the length
+fn parse_token(text: &str) -> Option<(usize, usize)> {
+    let inner = text.strip_prefix('{').or_else(|| text.strip_prefix('｛'))?;
+    let digits_start = inner.len() - inner.trim_start().len();
+    let digits = &inner[digits_start..];
+    let digits_length = digits.len()
+        - digits
+            .trim_start_matches(|c: char| c.is_ascii_digit())
+            .len();
+    let index = digits[..digits_length].parse().ok()?;
+    let after = digits[digits_length..].trim_start();
+    let close = after
+        .strip_prefix('}')
+        .or_else(|| after.strip_prefix('｝'))?;
+    Some((index, text.len() - close.len()))
+}
+
+// masks the placeholders of the common message formats
+// `{name}` and icu `{count, plural, ...}`, `{{name}}` of i18next, `%{name}` of rails, `$t(key)`,
+// `@:key` of vue-i18n, printf style `%s`, `%1$d`, `%.2f` and `%@` and `%#@files@` of apple
+pub(crate) fn mask_placeholders(text: &str) -> Masked {
+    let mut masked = Masked::default();
+    masked.push_text_masking_placeholders(text, str::to_string);
+    masked
+}
+
+// length of the placeholder at the start of the text, 0 if there is none
+fn placeholder_length(text: &str) -> usize {
+    let braces = |text: &str| {
+        let mut depth = 0;
+        for (offset, c) in text.char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return offset + 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        0
+    };
+    let key_length = |text: &str| {
+        text.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.' || c == '-'))
+            .unwrap_or(text.len())
+    };
+    if text.starts_with('{') {
+        braces(text)
+    } else if text.starts_with("%{") {
+        1 + braces(&text[1..])
+    } else if text.starts_with("$t(") {
+        text.find(')').map_or(0, |end| end + 1)
+    } else if let Some(key) = text.strip_prefix("@:") {
+        match key_length(key) {
+            0 => 0,
+            length => 2 + length,
+        }
+    } else if let Some(format) = text.strip_prefix('%') {
+        printf_length(format).map_or(0, |length| 1 + length)
+    } else {
+        0
+    }
+}
+
+// length of a printf conversion after the percent sign
+fn printf_length(format: &str) -> Option<usize> {
+    if format.starts_with('%') {
+        return Some(1);
+    }
+    if let Some(variable) = format.strip_prefix("#@") {
+        return variable.find('@').map(|end| end + 3);
+    }
+    let bytes = format.as_bytes();
+    let digits = |position: usize| {
+        bytes[position.min(bytes.len())..]
+            .iter()
+            .take_while(|byte| byte.is_ascii_digit())
+            .count()
+    };
+    let mut position = 0;
+    let argument = digits(0);
+    if argument > 0 && bytes.get(argument) == Some(&b'$') {
+        position = argument + 1;
+    } else if let Some(key) = format.strip_prefix('(') {
+        // a python mapping key like %(name)s
+        position = key.find(')')? + 2;
+    }
+    while matches!(bytes.get(position), Some(b'-' | b'+' | b'#' | b'0')) {
+        position += 1;
+    }
+    position += digits(position);
+    if bytes.get(position) == Some(&b'.') {
+        position += 1 + digits(position + 1);
+    }
+    while matches!(
+        bytes.get(position),
+        Some(b'l' | b'h' | b'q' | b'z' | b't' | b'j' | b'L')
+    ) {
+        position += 1;
+    }
+    match bytes.get(position) {
+        Some(
+            b'd' | b'i' | b'u' | b'o' | b'x' | b'X' | b'f' | b'F' | b'e' | b'E' | b'g' | b'G'
+            | b'a' | b'A' | b'c' | b's' | b'p' | b'@',
+        ) => Some(position + 1),
+        _ => None,
+    }
+}
@@ -0,0 +1,74 @@
+// Copyright (C) 2022  float3
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+// cancels every call the token or one of its clones was passed to
+// calls notice it between attempts, a request already on the wire runs until its timeout
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        let (cancelled, condvar) = &*self.inner;
+        *cancelled
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = true;
+        condvar.notify_all();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self
+            .inner
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // sleeps for the duration unless cancelled first, returns whether it was cancelled
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let (cancelled, condvar) = &*self.inner;
+        // None for a duration too long to have an end, that only ends by cancelling
+        let end = Instant::now().checked_add(duration);
+        let mut guard = cancelled
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        while !*guard {
+            let now = Instant::now();
+            guard = match end {
+                Some(end) if now >= end => break,
+                Some(end) => {
+                    condvar
+                        .wait_timeout(guard, end - now)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                        .0
+                }
+                None => condvar
+                    .wait(guard)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner()),
+            };
+        }
+        *guard
+    }
+}
@@ -0,0 +1,178 @@
+// Copyright (C) 2022  float3
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::lang::{LanguageCode, TargetLanguage};
+
+// what translate.google.com offers per language as of 2022-11-15
+// auto is only known after detection, so source dependent features report false for it
+
+// batchexecute rpcs the web app uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rpc {
+    Translate,
+    TextToSpeech,
+}
+
+impl Rpc {
+    pub fn id(&self) -> &'static str {
+        match self {
+            Rpc::Translate => "MkEWBc",
+            Rpc::TextToSpeech => "jQ1olc",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Capabilities {
+    pub translation: bool,
+    pub source_transliteration: bool,
+    pub target_transliteration: bool,
+    pub source_speech: bool,
+    pub target_speech: bool,
+    pub dictionary: bool,
+    pub definitions: bool,
+    pub examples: bool,
+    pub gendered_translations: bool,
+}
+
+impl Capabilities {
+    pub fn supports(&self, rpc: Rpc) -> bool {
+        match rpc {
+            Rpc::Translate => self.translation,
+            Rpc::TextToSpeech => self.source_speech || self.target_speech,
+        }
+    }
+
+    pub fn rpcs(&self) -> Vec<Rpc> {
+        [Rpc::Translate, Rpc::TextToSpeech]
+            .into_iter()
+            .filter(|rpc| self.supports(*rpc))
+            .collect()
+    }
+}
+
+pub fn capabilities(source: LanguageCode, target: TargetLanguage) -> Capabilities {
+    let target = target.language_code();
+    let known_source = source != LanguageCode::auto;
+    Capabilities {
+        translation: source != target,
+        source_transliteration: known_source && has_transliteration(source),
+        target_transliteration: has_transliteration(target),
+        source_speech: known_source && has_speech(source),
+        target_speech: has_speech(target),
+        dictionary: known_source && source != target && has_dictionary(source, target),
+        definitions: known_source && has_definitions(source),
+        examples: known_source && has_definitions(source),
+        gendered_translations: has_gendered_translations(source, target),
+    }
+}
+
+// romanization is shown for everything not written in latin script
+fn has_transliteration(lang: LanguageCode) -> bool {
+    lang.info().script != "Latn"
+}
+
+fn has_speech(lang: LanguageCode) -> bool {
+    use LanguageCode::*;
+    matches!(
+        lang,
+        af | sq
+            | am
+            | ar
+            | bn
+            | bs
+            | bg
+            | ca
+            | zh_CN
+            | zh_TW
+            | hr
+            | cs
+            | da
+            | nl
+            | en
+            | et
+            | tl
+            | fi
+            | fr
+            | gl
+            | de
+            | el
+            | gu
+            | iw
+            | hi
+            | hu
+            | is
+            | id
+            | it
+            | ja
+            | jw
+            | kn
+            | km
+            | ko
+            | la
+            | lv
+            | lt
+            | ms
+            | ml
+            | mr
+            | my
+            | ne
+            | no
+            | pl
+            | pt
+            | pa
+            | ro
+            | ru
+            | sr
+            | si
+            | sk
+            | es
+            | su
+            | sw
+            | sv
+            | ta
+            | te
+            | th
+            | tr
+            | uk
+            | ur
+            | vi
+            | cy
+    )
+}
+
+fn has_definitions(lang: LanguageCode) -> bool {
+    use LanguageCode::*;
+    matches!(
+        lang,
+        en | de | es | fr | it | pt | ru | nl | pl | tr | ar | hi | ja | ko | zh_CN
+    )
+}
+
+// the bilingual dictionary only exists for pairs with english on one side
+fn has_dictionary(source: LanguageCode, target: LanguageCode) -> bool {
+    match (source, target) {
+        (LanguageCode::en, other) | (other, LanguageCode::en) => has_definitions(other),
+        _ => false,
+    }
+}
+
+fn has_gendered_translations(source: LanguageCode, target: LanguageCode) -> bool {
+    use LanguageCode::*;
+    matches!(
+        (source, target),
+        (en, de | es | fr | it | pt) | (tr | fi | hu | fa, en)
+    )
+}
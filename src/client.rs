@@ -0,0 +1,809 @@
+// Copyright (C) 2022  float3
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    error::Error,
+    path::PathBuf,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Method,
+};
+use urlencoding::encode;
+
+use crate::{
+    cancellation::CancellationToken,
+    cookies::CookieJar,
+    error::{detect_block_page, parse_retry_after, BlockPage, RequestError},
+    headers::{HeaderRotation, HeaderSet, DEFAULT_REFERER, DEFAULT_USER_AGENT},
+    lang::{LanguageCode, TargetLanguage},
+    package_rpc, parse_json, parse_translation,
+    proxy::{ProxyPool, ProxyRotation},
+    rate_limit::{RateLimit, RateLimiter},
+    retry::RetryPolicy,
+    session::{Session, SessionTokens, GOOGLETRANSLATEHOME},
+    transport::{
+        HttpRequest, HttpResponse, ReqwestTransport, Transport, TransportError, TransportErrorKind,
+    },
+    GOOGLETRANSLATEURL, GOOGLETTSRPC, MAX_TEXT_LENGTH,
+};
+
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone)]
+pub struct Client {
+    http: Arc<ProxyPool>,
+    headers: HeaderMap,
+    header_rotation: Arc<HeaderRotation>,
+    retry_policy: RetryPolicy,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    read_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    session: Option<Arc<Session>>,
+    cookie_jar: Arc<CookieJar>,
+    cookie_file: Option<PathBuf>,
+    handle_consent: bool,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Client {
+    // panics if the underlying http client can not be initialized, use the builder to handle that
+    pub fn new() -> Self {
+        Self::builder()
+            .build()
+            .expect("failed to build google translate client")
+    }
+
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+
+    pub fn cookie_jar(&self) -> &Arc<CookieJar> {
+        &self.cookie_jar
+    }
+
+    // writes the jar to the file given to the builder, if any
+    pub fn save_cookies(&self) -> Result<(), Box<dyn Error>> {
+        match &self.cookie_file {
+            Some(cookie_file) => self.cookie_jar.save(cookie_file),
+            None => Result::Ok(()),
+        }
+    }
+
+    // sets the consent cookies once and asks for another attempt when google still shows the interstitial
+    fn retry_with_consent<T>(
+        &self,
+        result: &Result<T, Box<dyn Error>>,
+        consent_retried: &mut bool,
+    ) -> bool {
+        if !self.handle_consent || *consent_retried {
+            return false;
+        }
+        let consent_page = matches!(
+            result
+                .as_ref()
+                .err()
+                .and_then(|error| error.downcast_ref::<RequestError>()),
+            Some(RequestError::Blocked {
+                page: BlockPage::Consent,
+                ..
+            })
+        );
+        if consent_page {
+            self.cookie_jar.set_consent_cookies();
+            *consent_retried = true;
+        }
+        consent_page
+    }
+
+    // defaults, then the next rotated header set, then the headers set on the builder
+    fn base_headers(&self) -> HeaderMap {
+        let mut headers: HeaderMap = HeaderMap::new();
+        headers.insert(
+            reqwest::header::REFERER,
+            HeaderValue::from_static(DEFAULT_REFERER),
+        );
+        headers.insert(
+            reqwest::header::USER_AGENT,
+            HeaderValue::from_static(DEFAULT_USER_AGENT),
+        );
+        if let Some(header_set) = self.header_rotation.next() {
+            headers.extend(header_set.clone());
+        }
+        headers.extend(self.headers.clone());
+        headers
+    }
+
+    pub(crate) fn request_headers(
+        &self,
+        content_length: usize,
+    ) -> Result<HeaderMap, Box<dyn Error>> {
+        let mut headers = self.base_headers();
+        headers.insert(
+            reqwest::header::CONTENT_TYPE,
+            HeaderValue::from_static("application/x-www-form-urlencoded;charset=utf-8"),
+        );
+        headers.insert(
+            reqwest::header::CONTENT_LENGTH,
+            HeaderValue::from_str(content_length.to_string().as_str())?,
+        );
+        Result::Ok(headers)
+    }
+
+    // the earlier of the deadline of the call and the timeout of the client
+    fn deadline(&self, options: &RequestOptions) -> Option<Instant> {
+        match (options.deadline, self.timeout) {
+            (Some(deadline), Some(timeout)) => Some(deadline.min(Instant::now() + timeout)),
+            (deadline, timeout) => {
+                deadline.or_else(|| timeout.map(|timeout| Instant::now() + timeout))
+            }
+        }
+    }
+
+    fn web_request(
+        &self,
+        bytes: Vec<u8>,
+        deadline: Option<Instant>,
+        options: &RequestOptions,
+    ) -> Result<String, Box<dyn Error>> {
+        let mut attempt = 0;
+        let mut consent_retried = false;
+        loop {
+            options.check(deadline)?;
+            if let Some(rate_limiter) = &self.rate_limiter {
+                while let Some(wait) = rate_limiter.request_wait() {
+                    options.sleep(wait, deadline)?;
+                }
+            }
+            let (proxy, http) = self.http.select();
+            let remaining =
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            let timeout = min_timeout(self.read_timeout, remaining);
+            let mut url = GOOGLETRANSLATEURL.to_string();
+            let mut body = bytes.clone();
+            if let Some(session) = &self.session {
+                let tokens = match session.tokens() {
+                    Some(tokens) => tokens,
+                    None => {
+                        let mut tokens = self.bootstrap(http, timeout);
+                        if self.retry_with_consent(&tokens, &mut consent_retried) {
+                            tokens = self.bootstrap(http, timeout);
+                        }
+                        match tokens {
+                            Ok(tokens) => {
+                                session.set_tokens(Some(tokens.clone()));
+                                tokens
+                            }
+                            // the home page goes through the same retries as the rpc
+                            Err(error) => {
+                                let (retryable, retry_after) = match error.downcast_ref() {
+                                    Some(RequestError::Status {
+                                        status,
+                                        retry_after,
+                                    }) => (
+                                        self.retry_policy.is_retryable_status(*status),
+                                        *retry_after,
+                                    ),
+                                    _ => (
+                                        error.downcast_ref().is_some_and(|error| {
+                                            self.retry_policy.is_retryable_error(error)
+                                        }),
+                                        None,
+                                    ),
+                                };
+                                if retryable {
+                                    self.http.report_failure(proxy);
+                                }
+                                attempt += 1;
+                                if !retryable || attempt >= self.retry_policy.max_attempts {
+                                    return Result::Err(timed_out(error, deadline));
+                                }
+                                options.sleep(self.retry_delay(attempt, retry_after), deadline)?;
+                                continue;
+                            }
+                        }
+                    }
+                };
+                let query: Vec<String> = session
+                    .query(GOOGLETTSRPC, &tokens)
+                    .iter()
+                    .map(|(name, value)| format!("{}={}", name, encode(value)))
+                    .collect();
+                url = format!("{}?{}", url, query.join("&"));
+                if let Some(at) = &tokens.at {
+                    body.extend(format!("at={}&", encode(at)).into_bytes());
+                }
+            }
+            let result = http.send(HttpRequest {
+                method: Method::POST,
+                url,
+                headers: self.request_headers(body.len())?,
+                body,
+                timeout,
+            });
+            if let Some(session) = &self.session {
+                // stale tokens are a likely cause, fetch fresh ones for the next attempt
+                if !result
+                    .as_ref()
+                    .is_ok_and(|response| (200..300).contains(&response.status))
+                {
+                    session.set_tokens(None);
+                }
+            }
+            let (retryable, retry_after) = match &result {
+                Ok(response) => (
+                    self.retry_policy.is_retryable_status(response.status),
+                    retry_after(response),
+                ),
+                Err(error) => (self.retry_policy.is_retryable_error(error), None),
+            };
+            if retryable || result.is_err() {
+                self.http.report_failure(proxy);
+            }
+            attempt += 1;
+            if !retryable || attempt >= self.retry_policy.max_attempts {
+                let response = result.map_err(|error| timed_out(Box::new(error), deadline))?;
+                let checked = check_response(response);
+                if self.retry_with_consent(&checked, &mut consent_retried) {
+                    continue;
+                }
+                return checked;
+            }
+            options.sleep(self.retry_delay(attempt, retry_after), deadline)?;
+        }
+    }
+
+    // the backoff before the next attempt, or the wait google asked for if that is longer
+    fn retry_delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        let backoff = self.retry_policy.backoff(attempt - 1);
+        let hinted = retry_after
+            .unwrap_or_default()
+            .min(self.retry_policy.max_backoff);
+        backoff.max(hinted)
+    }
+
+    fn bootstrap(
+        &self,
+        http: &dyn Transport,
+        timeout: Option<Duration>,
+    ) -> Result<SessionTokens, Box<dyn Error>> {
+        let response = http.send(HttpRequest {
+            method: Method::GET,
+            url: GOOGLETRANSLATEHOME.to_string(),
+            headers: self.base_headers(),
+            body: Vec::new(),
+            timeout,
+        })?;
+        let body = String::from_utf8_lossy(&response.body);
+        if let Some(tokens) = SessionTokens::parse(&body) {
+            return Result::Ok(tokens);
+        }
+        match detect_block_page(&response.url, &body) {
+            Some(page @ (BlockPage::Captcha | BlockPage::Consent)) => {
+                Result::Err(Box::new(RequestError::Blocked {
+                    page,
+                    status: response.status,
+                    retry_after: retry_after(&response),
+                }))
+            }
+            _ if !(200..300).contains(&response.status) => {
+                Result::Err(Box::new(RequestError::Status {
+                    status: response.status,
+                    retry_after: retry_after(&response),
+                }))
+            }
+            _ => Result::Err("no session tokens found on translate.google.com".into()),
+        }
+    }
+
+    fn get_response(
+        &self,
+        text: &str,
+        source_language: LanguageCode,
+        target_language: TargetLanguage,
+        options: &RequestOptions,
+    ) -> Result<String, Box<dyn Error>> {
+        if text.is_empty() {
+            return Result::Err("text is empty".into());
+        };
+        if text.len() > MAX_TEXT_LENGTH {
+            return Result::Err("text can not be longer than 5000 characters".into());
+        };
+        let deadline = self.deadline(options);
+        if let Some(rate_limiter) = &self.rate_limiter {
+            while let Some(wait) = rate_limiter.characters_wait(text.chars().count()) {
+                options.sleep(wait, deadline)?;
+            }
+        }
+        let bytes = package_rpc(text, source_language, target_language);
+        let response_text = self.web_request(bytes, deadline, options)?;
+        let json = rpc_response_line(&response_text)
+            .ok_or("no last")?
+            .to_string();
+        Result::Ok(json)
+    }
+
+    // text has to between in the range of [1,5000]
+    pub fn translate(
+        &self,
+        text: &str,
+        source_language: LanguageCode,
+        target_language: TargetLanguage,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        self.translate_with(
+            text,
+            source_language,
+            target_language,
+            &RequestOptions::default(),
+        )
+    }
+
+    pub fn translate_with(
+        &self,
+        text: &str,
+        source_language: LanguageCode,
+        target_language: TargetLanguage,
+        options: &RequestOptions,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let json = self.get_response(text, source_language, target_language, options)?;
+        let translations = parse_json(json.as_str())?;
+
+        Result::Ok(translations)
+    }
+
+    // the whole text translated, where translate only returns alternatives for the first sentence
+    pub fn translate_text(
+        &self,
+        text: &str,
+        source_language: LanguageCode,
+        target_language: TargetLanguage,
+    ) -> Result<String, Box<dyn Error>> {
+        self.translate_text_with(
+            text,
+            source_language,
+            target_language,
+            &RequestOptions::default(),
+        )
+    }
+
+    pub fn translate_text_with(
+        &self,
+        text: &str,
+        source_language: LanguageCode,
+        target_language: TargetLanguage,
+        options: &RequestOptions,
+    ) -> Result<String, Box<dyn Error>> {
+        let json = self.get_response(text, source_language, target_language, options)?;
+        parse_translation(json.as_str())
+    }
+}
+
+// per call settings on top of the client configuration
+#[derive(Debug, Clone, Default)]
+pub struct RequestOptions {
+    deadline: Option<Instant>,
+    cancellation: Option<CancellationToken>,
+}
+
+impl RequestOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the earlier of this and the client timeout wins
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.deadline(Instant::now() + timeout)
+    }
+
+    pub fn cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    pub(crate) fn check(&self, deadline: Option<Instant>) -> Result<(), RequestError> {
+        if self
+            .cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+        {
+            return Result::Err(RequestError::Cancelled);
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Result::Err(RequestError::Timeout);
+        }
+        Result::Ok(())
+    }
+
+    fn sleep(&self, duration: Duration, deadline: Option<Instant>) -> Result<(), RequestError> {
+        let duration = min_timeout(
+            Some(duration),
+            deadline.map(|deadline| deadline.saturating_duration_since(Instant::now())),
+        )
+        .unwrap_or(duration);
+        match &self.cancellation {
+            Some(cancellation) => {
+                cancellation.sleep(duration);
+            }
+            None => thread::sleep(duration),
+        }
+        self.check(deadline)
+    }
+}
+
+// a transport timeout after the deadline passed is the deadline of the call running out
+fn timed_out(error: Box<dyn Error>, deadline: Option<Instant>) -> Box<dyn Error> {
+    let timeout = error
+        .downcast_ref::<TransportError>()
+        .is_some_and(|error| error.kind() == TransportErrorKind::Timeout);
+    if timeout && deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+        return Box::new(RequestError::Timeout);
+    }
+    error
+}
+
+fn min_timeout(a: Option<Duration>, b: Option<Duration>) -> Option<Duration> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+// with rt=c the rpc result is one of several length prefixed chunks
+pub(crate) fn rpc_response_line(response_text: &str) -> Option<&str> {
+    response_text
+        .lines()
+        .find(|line| line.starts_with("[[\"wrb.fr\""))
+        .or_else(|| response_text.split('\n').next_back())
+}
+
+fn retry_after(response: &HttpResponse) -> Option<Duration> {
+    response
+        .headers
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_retry_after)
+}
+
+// google answers with html instead of json when it blocks us, often with a 200
+fn check_response(response: HttpResponse) -> Result<String, Box<dyn Error>> {
+    let retry_after = retry_after(&response);
+    let body = String::from_utf8_lossy(&response.body).into_owned();
+    if let Some(page) = detect_block_page(&response.url, &body) {
+        return Result::Err(Box::new(RequestError::Blocked {
+            page,
+            status: response.status,
+            retry_after,
+        }));
+    }
+    if !(200..300).contains(&response.status) {
+        return Result::Err(Box::new(RequestError::Status {
+            status: response.status,
+            retry_after,
+        }));
+    }
+    Result::Ok(body)
+}
+
+pub struct ClientBuilder {
+    retry_policy: RetryPolicy,
+    rate_limit: Option<RateLimit>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    proxies: Vec<String>,
+    proxy_rotation: ProxyRotation,
+    headers: Vec<(String, String)>,
+    header_sets: Vec<HeaderSet>,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    session_bootstrap: bool,
+    host_language: String,
+    cookie_jar: Option<Arc<CookieJar>>,
+    cookie_file: Option<PathBuf>,
+    handle_consent: bool,
+    root_certificates: Vec<Vec<u8>>,
+    built_in_root_certificates: bool,
+    transport: Option<Arc<dyn Transport>>,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        ClientBuilder {
+            retry_policy: RetryPolicy::default(),
+            rate_limit: None,
+            rate_limiter: None,
+            proxies: Vec::new(),
+            proxy_rotation: ProxyRotation::default(),
+            headers: Vec::new(),
+            header_sets: Vec::new(),
+            connect_timeout: Some(DEFAULT_CONNECT_TIMEOUT),
+            read_timeout: Some(Duration::from_secs(30)),
+            timeout: Some(Duration::from_secs(120)),
+            session_bootstrap: false,
+            host_language: "en".to_string(),
+            cookie_jar: None,
+            cookie_file: None,
+            handle_consent: true,
+            root_certificates: Vec::new(),
+            built_in_root_certificates: true,
+            transport: None,
+        }
+    }
+}
+
+impl ClientBuilder {
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    // checked by build, which fails for rates that are not positive
+    pub fn rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self.rate_limiter = None;
+        self
+    }
+
+    // share one budget between several clients
+    pub fn rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self.rate_limit = None;
+        self
+    }
+
+    // http://, https://, socks5:// or socks5h:// urls, credentials go into the url
+    pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.proxies.push(proxy.into());
+        self
+    }
+
+    pub fn proxies<I, S>(mut self, proxies: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.proxies.extend(proxies.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn proxy_rotation(mut self, proxy_rotation: ProxyRotation) -> Self {
+        self.proxy_rotation = proxy_rotation;
+        self
+    }
+
+    // overrides the default and rotated headers on every request
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn user_agent(self, user_agent: impl Into<String>) -> Self {
+        self.header(reqwest::header::USER_AGENT.as_str(), user_agent)
+    }
+
+    // one set is picked per request, round robin
+    pub fn header_sets(mut self, header_sets: Vec<HeaderSet>) -> Self {
+        self.header_sets = header_sets;
+        self
+    }
+
+    pub fn rotate_browser_headers(self) -> Self {
+        self.header_sets(HeaderSet::browsers())
+    }
+
+    pub fn connect_timeout(mut self, connect_timeout: Option<Duration>) -> Self {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    // limit for a single attempt, from sending the request until the body is read
+    pub fn read_timeout(mut self, read_timeout: Option<Duration>) -> Self {
+        self.read_timeout = read_timeout;
+        self
+    }
+
+    // limit for a whole call including retries, backoff and rate limiting
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // load translate.google.com once and send its session tokens along like the web app does
+    pub fn session_bootstrap(mut self, session_bootstrap: bool) -> Self {
+        self.session_bootstrap = session_bootstrap;
+        self
+    }
+
+    // the hl parameter, the interface language of the emulated web app
+    pub fn host_language(mut self, host_language: impl Into<String>) -> Self {
+        self.host_language = host_language.into();
+        self
+    }
+
+    // share one jar between several clients
+    pub fn cookie_jar(mut self, cookie_jar: Arc<CookieJar>) -> Self {
+        self.cookie_jar = Some(cookie_jar);
+        self
+    }
+
+    // cookies are loaded from this file when it exists and written back by Client::save_cookies
+    pub fn cookie_file(mut self, cookie_file: impl Into<PathBuf>) -> Self {
+        self.cookie_file = Some(cookie_file.into());
+        self
+    }
+
+    // answer the eu consent interstitial instead of failing with BlockPage::Consent
+    pub fn handle_consent(mut self, handle_consent: bool) -> Self {
+        self.handle_consent = handle_consent;
+        self
+    }
+
+    // pem or der encoded, e.g. the root of a tls inspecting corporate proxy
+    pub fn add_root_certificate(mut self, certificate: impl Into<Vec<u8>>) -> Self {
+        self.root_certificates.push(certificate.into());
+        self
+    }
+
+    // turn off to trust only the certificates added with add_root_certificate
+    pub fn built_in_root_certificates(mut self, built_in_root_certificates: bool) -> Self {
+        self.built_in_root_certificates = built_in_root_certificates;
+        self
+    }
+
+    // replaces the reqwest based default, proxies, cookie_jar, cookie_file, root certificates
+    // and connect_timeout are then up to the transport and build fails if any of them is set
+    // read_timeout still applies as the timeout of each HttpRequest
+    pub fn transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    // the first setting that only the reqwest transport applies
+    fn reqwest_setting(&self) -> Option<&'static str> {
+        if self.cookie_jar.is_some() {
+            Some("a cookie jar")
+        } else if self.cookie_file.is_some() {
+            Some("a cookie file")
+        } else if !self.root_certificates.is_empty() {
+            Some("root certificates")
+        } else if !self.built_in_root_certificates {
+            Some("turning off the built-in root certificates")
+        } else if self.connect_timeout != Some(DEFAULT_CONNECT_TIMEOUT) {
+            Some("a connect timeout")
+        } else {
+            None
+        }
+    }
+
+    fn http_client(
+        &self,
+        cookie_jar: &Arc<CookieJar>,
+    ) -> Result<reqwest::blocking::ClientBuilder, Box<dyn Error>> {
+        let builder = reqwest::blocking::Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.read_timeout)
+            .cookie_provider(Arc::clone(cookie_jar));
+        self.tls(builder)
+    }
+
+    #[cfg(feature = "__tls")]
+    fn tls(
+        &self,
+        mut builder: reqwest::blocking::ClientBuilder,
+    ) -> Result<reqwest::blocking::ClientBuilder, Box<dyn Error>> {
+        for certificate in &self.root_certificates {
+            let certificate = if certificate.starts_with(b"-----BEGIN") {
+                reqwest::Certificate::from_pem(certificate)?
+            } else {
+                reqwest::Certificate::from_der(certificate)?
+            };
+            builder = builder.add_root_certificate(certificate);
+        }
+        Result::Ok(builder.tls_built_in_root_certs(self.built_in_root_certificates))
+    }
+
+    #[cfg(not(feature = "__tls"))]
+    fn tls(
+        &self,
+        builder: reqwest::blocking::ClientBuilder,
+    ) -> Result<reqwest::blocking::ClientBuilder, Box<dyn Error>> {
+        if !self.root_certificates.is_empty() {
+            return Result::Err(
+                "root certificates need one of the rustls-tls, rustls-tls-native-roots or native-tls features".into(),
+            );
+        }
+        Result::Ok(builder)
+    }
+
+    pub fn build(self) -> Result<Client, Box<dyn Error>> {
+        let cookie_jar = match (&self.cookie_jar, &self.cookie_file) {
+            (Some(cookie_jar), _) => Arc::clone(cookie_jar),
+            (None, Some(cookie_file)) if cookie_file.exists() => {
+                Arc::new(CookieJar::load(cookie_file)?)
+            }
+            (None, _) => Arc::new(CookieJar::new()),
+        };
+        if self.handle_consent && !cookie_jar.has_consent_cookies() {
+            cookie_jar.set_consent_cookies();
+        }
+        let transports: Vec<Arc<dyn Transport>> = match (&self.transport, self.proxies.is_empty()) {
+            (Some(transport), true) => {
+                if let Some(setting) = self.reqwest_setting() {
+                    return Result::Err(
+                        format!("{} can not be combined with a custom transport", setting).into(),
+                    );
+                }
+                vec![Arc::clone(transport)]
+            }
+            (Some(_), false) => {
+                return Result::Err("proxies can not be combined with a custom transport".into())
+            }
+            (None, true) => vec![Arc::new(ReqwestTransport::new(
+                self.http_client(&cookie_jar)?.build()?,
+            ))],
+            (None, false) => self
+                .proxies
+                .iter()
+                .map(|proxy| {
+                    let client = self
+                        .http_client(&cookie_jar)?
+                        .proxy(reqwest::Proxy::all(proxy.as_str())?)
+                        .build()?;
+                    Result::Ok(Arc::new(ReqwestTransport::new(client)) as Arc<dyn Transport>)
+                })
+                .collect::<Result<_, Box<dyn Error>>>()?,
+        };
+        let rate_limiter = match (self.rate_limiter, self.rate_limit) {
+            (Some(rate_limiter), _) => Some(rate_limiter),
+            (None, Some(rate_limit)) => Some(Arc::new(RateLimiter::new(rate_limit)?)),
+            (None, None) => None,
+        };
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            headers.insert(
+                HeaderName::from_bytes(name.as_bytes())?,
+                HeaderValue::from_str(value)?,
+            );
+        }
+        Result::Ok(Client {
+            http: Arc::new(ProxyPool::new(transports, self.proxy_rotation)),
+            headers,
+            header_rotation: Arc::new(HeaderRotation::new(&self.header_sets)?),
+            retry_policy: self.retry_policy,
+            rate_limiter,
+            read_timeout: self.read_timeout,
+            timeout: self.timeout,
+            session: self
+                .session_bootstrap
+                .then(|| Arc::new(Session::new(self.host_language))),
+            cookie_jar,
+            cookie_file: self.cookie_file,
+            handle_consent: self.handle_consent,
+        })
+    }
+}
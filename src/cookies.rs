@@ -0,0 +1,103 @@
+// Copyright (C) 2022  float3
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+    sync::{Mutex, MutexGuard},
+};
+
+use cookie_store::{CookieStore, RawCookie};
+use reqwest::{header::HeaderValue, Url};
+
+// accepts the consent.google.com interstitial the way clicking "reject all" does
+const CONSENT_COOKIES: [&str; 2] = [
+    "SOCS=CAESEwgDEgk0ODE3Nzk3MjQaAmVuIAEaBgiA_LyaBg; Domain=.google.com; Path=/; Max-Age=33696000; Secure",
+    "CONSENT=PENDING+987; Domain=.google.com; Path=/; Max-Age=33696000; Secure",
+];
+
+// cookie jar shared by every request of a client, can be saved to and loaded from json
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    store: Mutex<CookieStore>,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        Result::Ok(CookieJar {
+            store: Mutex::new(CookieStore::load_json_all(reader).map_err(|e| e.to_string())?),
+        })
+    }
+
+    // session cookies are kept as well, so a saved jar continues the same session
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.lock()
+            .save_incl_expired_and_nonpersistent_json(&mut writer)
+            .map_err(|e| e.to_string())?;
+        Result::Ok(())
+    }
+
+    pub fn set_consent_cookies(&self) {
+        let url = Url::parse("https://www.google.com/").expect("valid url");
+        let mut store = self.lock();
+        for cookie in CONSENT_COOKIES {
+            let _ = store.parse(cookie, &url);
+        }
+    }
+
+    pub fn has_consent_cookies(&self) -> bool {
+        let url = Url::parse("https://translate.google.com/").expect("valid url");
+        self.lock()
+            .get_request_values(&url)
+            .any(|(name, _)| name == "SOCS")
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CookieStore> {
+        self.store
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl reqwest::cookie::CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        let cookies = cookie_headers.filter_map(|header| {
+            let header = header.to_str().ok()?;
+            RawCookie::parse(header.to_string()).ok()
+        });
+        self.lock().store_response_cookies(cookies, url);
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let header = self
+            .lock()
+            .get_request_values(url)
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("; ");
+        if header.is_empty() {
+            return None;
+        }
+        HeaderValue::from_str(&header).ok()
+    }
+}
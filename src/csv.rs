@@ -0,0 +1,315 @@
+// Copyright (C) 2022  float3
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+};
+
+use crate::{
+    batch::{mask_placeholders, translate_segments},
+    client::{Client, RequestOptions},
+    lang::{LanguageCode, TargetLanguage},
+};
+
+// translations kept between batches, the cache is cleared when it grows past this
+const CACHE_LIMIT: usize = 10_000;
+// the longest record in bytes, a quote that is never closed would otherwise read in the rest
+// of the file
+const RECORD_LIMIT: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CsvColumn {
+    // a column named in the header row
+    Name(String),
+    // a column by its position, counting from 0
+    Index(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    // ',' for csv and '\t' for tsv
+    pub delimiter: char,
+    // whether the first row names the columns
+    pub header: bool,
+    pub columns: Vec<CsvColumn>,
+    // rows read before their cells are translated together
+    pub batch_rows: usize,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: ',',
+            header: true,
+            columns: Vec::new(),
+            batch_rows: 500,
+        }
+    }
+}
+
+// translates the chosen columns of a csv or tsv file into every target language
+// each translated column is followed by one new column per target, named like `title_de` in
+// the header, rows are read and written in batches so the file is never held in memory
+// identical cells are only sent once, quoted fields with delimiters, quotes and line breaks
+// are read as rfc 4180 describes them and fields are quoted where they need it
+// a byte order mark in front of the header is kept in the output but is not part of its name
+pub fn translate_csv(
+    client: &Client,
+    input: impl Read,
+    output: impl Write,
+    source_language: LanguageCode,
+    target_languages: &[TargetLanguage],
+    csv: &CsvOptions,
+    options: &RequestOptions,
+) -> Result<(), Box<dyn Error>> {
+    if csv.columns.is_empty() {
+        return Result::Err("no columns chosen to translate".into());
+    }
+    let mut reader = Records {
+        input: BufReader::new(input),
+        delimiter: csv.delimiter,
+        line_break: None,
+        byte_order_mark: None,
+    };
+    let mut output = BufWriter::new(output);
+    let columns = if csv.header {
+        let Some(header) = reader.next_record()? else {
+            return Result::Ok(());
+        };
+        let columns = selected_columns(&csv.columns, Some(&header))?;
+        let mut row = header.clone();
+        insert_columns(&mut row, &columns, |column| {
+            target_languages
+                .iter()
+                .map(|target| {
+                    format!(
+                        "{}_{}",
+                        header.get(column).map(String::as_str).unwrap_or_default(),
+                        target.language_code().bcp_47()
+                    )
+                })
+                .collect()
+        });
+        reader.write_record(&mut output, &row)?;
+        columns
+    } else {
+        selected_columns(&csv.columns, None)?
+    };
+
+    let mut caches: Vec<HashMap<String, String>> = vec![HashMap::new(); target_languages.len()];
+    let mut rows = Vec::with_capacity(csv.batch_rows);
+    loop {
+        let record = reader.next_record()?;
+        let done = record.is_none();
+        rows.extend(record);
+        if rows.len() < csv.batch_rows.max(1) && !done {
+            continue;
+        }
+
+        for (target, cache) in target_languages.iter().zip(&mut caches) {
+            if cache.len() > CACHE_LIMIT {
+                cache.clear();
+            }
+            let masked: Vec<_> = rows
+                .iter()
+                .flat_map(|row: &Vec<String>| columns.iter().filter_map(|column| row.get(*column)))
+                .filter(|cell| !cache.contains_key(*cell))
+                .map(|cell| (cell, mask_placeholders(cell)))
+                .filter(|(_, masked)| masked.has_text())
+                .collect();
+            let segments: Vec<&str> = masked.iter().map(|(_, masked)| masked.text()).collect();
+            let translations =
+                translate_segments(client, &segments, source_language, *target, options)?;
+            for ((cell, masked), translation) in masked.iter().zip(translations) {
+                cache.insert(cell.to_string(), masked.unmask(&translation));
+            }
+        }
+
+        for mut row in rows.drain(..) {
+            let cells = row.clone();
+            insert_columns(&mut row, &columns, |column| {
+                let cell = cells.get(column).map(String::as_str).unwrap_or_default();
+                caches
+                    .iter()
+                    .map(|cache| cache.get(cell).cloned().unwrap_or_else(|| cell.to_string()))
+                    .collect()
+            });
+            reader.write_record(&mut output, &row)?;
+        }
+        if done {
+            break;
+        }
+    }
+    output.flush()?;
+    Result::Ok(())
+}
+
+// the indices of the chosen columns in ascending order
+fn selected_columns(
+    columns: &[CsvColumn],
+    header: Option<&Vec<String>>,
+) -> Result<Vec<usize>, Box<dyn Error>> {
+    let mut indices = columns
+        .iter()
+        .map(|column| match (column, header) {
+            (CsvColumn::Index(index), _) => Result::Ok(*index),
+            (CsvColumn::Name(name), Some(header)) => header
+                .iter()
+                .position(|cell| cell == name)
+                .ok_or_else(|| format!("no column named {:?}", name).into()),
+            (CsvColumn::Name(name), None) => {
+                Result::Err(format!("column {:?} chosen by name without a header", name).into())
+            }
+        })
+        .collect::<Result<Vec<usize>, Box<dyn Error>>>()?;
+    indices.sort_unstable();
+    indices.dedup();
+    Result::Ok(indices)
+}
+
+// inserts the cells `new` returns after each chosen column, short rows are padded
+fn insert_columns(row: &mut Vec<String>, columns: &[usize], new: impl Fn(usize) -> Vec<String>) {
+    if let Some(last) = columns.last() {
+        if row.len() <= *last {
+            row.resize(last + 1, String::new());
+        }
+    }
+    for column in columns.iter().rev() {
+        let cells = new(*column);
+        row.splice(column + 1..column + 1, cells);
+    }
+}
+
+struct Records<R> {
+    input: R,
+    delimiter: char,
+    // the line break of the first record, used for every written one
+    line_break: Option<&'static str>,
+    // whether the input starts with a byte order mark, None before it is read, false once it
+    // is written again
+    byte_order_mark: Option<bool>,
+}
+
+impl<R: BufRead> Records<R> {
+    // the fields of the next record, a quoted field can span several lines
+    fn next_record(&mut self) -> Result<Option<Vec<String>>, Box<dyn Error>> {
+        let mut record = Record::default();
+        let mut line = String::new();
+        let mut length = 0;
+        loop {
+            line.clear();
+            let read = (&mut self.input)
+                .take((RECORD_LIMIT + 1 - length) as u64)
+                .read_line(&mut line)?;
+            if read == 0 {
+                // at the end of the input an open quote ends with the text
+                return Result::Ok((length > 0).then(|| record.finish()));
+            }
+            length += read;
+            if length > RECORD_LIMIT {
+                return Result::Err(
+                    format!(
+                        "a record is longer than {} bytes, is a quote left open?",
+                        RECORD_LIMIT
+                    )
+                    .into(),
+                );
+            }
+            let mut text = line.as_str();
+            if self.byte_order_mark.is_none() {
+                let stripped = text.strip_prefix('\u{feff}');
+                self.byte_order_mark = Some(stripped.is_some());
+                text = stripped.unwrap_or(text);
+            }
+            if record.push_line(text, self.delimiter) {
+                if self.line_break.is_none() {
+                    self.line_break = Some(if text.ends_with("\r\n") { "\r\n" } else { "\n" });
+                }
+                return Result::Ok(Some(record.finish()));
+            }
+        }
+    }
+
+    fn write_record(&mut self, output: &mut impl Write, fields: &[String]) -> std::io::Result<()> {
+        let mut line = String::new();
+        if self.byte_order_mark == Some(true) {
+            line.push('\u{feff}');
+            self.byte_order_mark = Some(false);
+        }
+        for (index, field) in fields.iter().enumerate() {
+            if index > 0 {
+                line.push(self.delimiter);
+            }
+            if field.contains([self.delimiter, '"', '\r', '\n']) {
+                line.push('"');
+                line.push_str(&field.replace('"', "\"\""));
+                line.push('"');
+            } else {
+                line.push_str(field);
+            }
+        }
+        line.push_str(self.line_break.unwrap_or("\n"));
+        output.write_all(line.as_bytes())
+    }
+}
+
+// the fields of a record read so far
+#[derive(Default)]
+struct Record {
+    fields: Vec<String>,
+    field: String,
+    quoted: bool,
+    // a quote only opens a quoted field at its start
+    in_field: bool,
+}
+
+impl Record {
+    // reads the fields of one line, true once the record ends with it
+    fn push_line(&mut self, line: &str, delimiter: char) -> bool {
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            if self.quoted {
+                if c != '"' {
+                    self.field.push(c);
+                } else if chars.next_if_eq(&'"').is_some() {
+                    self.field.push('"');
+                } else {
+                    self.quoted = false;
+                }
+                continue;
+            }
+            match c {
+                '"' if !self.in_field => self.quoted = true,
+                '\r' if chars.peek() == Some(&'\n') => {}
+                '\n' => return true,
+                c if c == delimiter => {
+                    self.fields.push(std::mem::take(&mut self.field));
+                    self.in_field = false;
+                    continue;
+                }
+                c => self.field.push(c),
+            }
+            self.in_field = true;
+        }
+        false
+    }
+
+    fn finish(mut self) -> Vec<String> {
+        self.fields.push(self.field);
+        self.fields
+    }
+}
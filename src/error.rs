@@ -0,0 +1,106 @@
+// Copyright (C) 2022  float3
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{error::Error, fmt, time::Duration};
+
+// pages google serves instead of batchexecute json when it refuses to answer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockPage {
+    // the "unusual traffic from your computer network" page at google.com/sorry
+    Captcha,
+    // the consent.google.com cookie interstitial served to eu traffic
+    Consent,
+    // any other html document
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RequestError {
+    Blocked {
+        page: BlockPage,
+        status: u16,
+        retry_after: Option<Duration>,
+    },
+    Status {
+        status: u16,
+        retry_after: Option<Duration>,
+    },
+    // the deadline of the call passed before google answered
+    Timeout,
+    Cancelled,
+}
+
+impl RequestError {
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            RequestError::Blocked { retry_after, .. }
+            | RequestError::Status { retry_after, .. } => *retry_after,
+            RequestError::Timeout | RequestError::Cancelled => None,
+        }
+    }
+
+    pub fn is_blocked(&self) -> bool {
+        matches!(self, RequestError::Blocked { .. })
+    }
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::Blocked { page, status, .. } => write!(
+                f,
+                "request blocked by google ({:?} page, status {})",
+                page, status
+            )?,
+            RequestError::Status { status, .. } => write!(f, "unexpected status {}", status)?,
+            RequestError::Timeout => write!(f, "request timed out")?,
+            RequestError::Cancelled => write!(f, "request was cancelled")?,
+        }
+        if let Some(retry_after) = self.retry_after() {
+            write!(f, ", retry after {}s", retry_after.as_secs())?;
+        }
+        Result::Ok(())
+    }
+}
+
+impl Error for RequestError {}
+
+pub(crate) fn detect_block_page(url: &str, body: &str) -> Option<BlockPage> {
+    // rpc payloads echo the translated text, which may well mention any of the markers below
+    let start = body.trim_start();
+    let body = match start.starts_with(")]}'") || start.starts_with("[[\"wrb.fr\"") {
+        true => "",
+        false => body,
+    };
+    if url.contains("consent.google.") || body.contains("consent.google.") {
+        return Some(BlockPage::Consent);
+    }
+    if url.contains("/sorry/")
+        || body.contains("unusual traffic")
+        || body.contains("g-recaptcha")
+        || body.contains("/sorry/index")
+    {
+        return Some(BlockPage::Captcha);
+    }
+    if body.trim_start().starts_with('<') {
+        return Some(BlockPage::Unknown);
+    }
+    None
+}
+
+// only the delta-seconds form of the header, http dates are ignored
+pub(crate) fn parse_retry_after(value: &str) -> Option<Duration> {
+    value.trim().parse::<u64>().ok().map(Duration::from_secs)
+}
@@ -0,0 +1,326 @@
+// Copyright (C) 2022  float3
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{collections::HashMap, error::Error, ops::Range};
+
+use crate::{
+    batch::{translate_segments, Masked},
+    client::{Client, RequestOptions},
+    lang::{LanguageCode, TargetLanguage},
+};
+
+// translates the values and attributes of the messages in a fluent resource
+// placeables like `{ $name }` and `{ -brand }`, selectors and variant keys are never sent,
+// terms, comments and the layout of the file are kept
+// messages and terms that `existing` already has are copied from it instead of being translated,
+// so the result can replace the target file
+pub fn translate_fluent(
+    client: &Client,
+    resource: &str,
+    existing: Option<&str>,
+    source_language: LanguageCode,
+    target_language: TargetLanguage,
+    options: &RequestOptions,
+) -> Result<String, Box<dyn Error>> {
+    let existing: HashMap<&str, &str> = existing
+        .map(|existing| {
+            entries(existing)
+                .into_iter()
+                .map(|entry| (entry.id, &existing[entry.range]))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    // byte ranges of the resource and what replaces them
+    let mut replacements: Vec<(Range<usize>, Option<&str>)> = Vec::new();
+    let mut units = Vec::new();
+    for entry in entries(resource) {
+        if let Some(translated) = existing.get(entry.id) {
+            replacements.push((entry.range, Some(translated)));
+        } else if !entry.id.starts_with('-') {
+            let mut scanner = Scanner {
+                source: resource,
+                units: Vec::new(),
+            };
+            scanner.pattern(entry.value_start, entry.range.end);
+            units.extend(scanner.units);
+        }
+    }
+    let masked: Vec<(Range<usize>, Masked)> = units
+        .into_iter()
+        .map(|unit| {
+            let mut masked = Masked::default();
+            for (placeable, range) in unit.pieces {
+                if placeable {
+                    masked.push_placeholder(&resource[range]);
+                } else {
+                    masked.push_text(&join_lines(&resource[range]));
+                }
+            }
+            (unit.range, masked)
+        })
+        .filter(|(_, masked)| masked.has_text())
+        .collect();
+    let segments: Vec<&str> = masked.iter().map(|(_, masked)| masked.text()).collect();
+    let translations =
+        translate_segments(client, &segments, source_language, target_language, options)?;
+    let translations: Vec<(Range<usize>, String)> = masked
+        .into_iter()
+        .zip(translations)
+        .map(|((range, masked), translation)| (range, masked.unmask(&translation)))
+        .collect();
+    replacements.extend(
+        translations
+            .iter()
+            .map(|(range, translation)| (range.clone(), Some(translation.as_str()))),
+    );
+    replacements.sort_by_key(|(range, _)| range.start);
+
+    let mut output = String::with_capacity(resource.len());
+    let mut position = 0;
+    for (range, replacement) in replacements {
+        output.push_str(&resource[position..range.start]);
+        output.push_str(replacement.unwrap_or_default());
+        position = range.end;
+    }
+    output.push_str(&resource[position..]);
+    Result::Ok(output)
+}
+
+struct Entry<'a> {
+    // starts with a dash for terms
+    id: &'a str,
+    // from the identifier to the end of the last line of the entry
+    range: Range<usize>,
+    value_start: usize,
+}
+
+// messages and terms, an entry starts with `id =` at the beginning of a line and goes on
+// over indented lines and unclosed placeables
+fn entries(resource: &str) -> Vec<Entry<'_>> {
+    let mut entries = Vec::new();
+    let mut line_start = 0;
+    while line_start < resource.len() {
+        let first_line_end = line_end(resource, line_start);
+        let line = &resource[line_start..first_line_end];
+        let Some(id) = identifier(line) else {
+            line_start = first_line_end + 1;
+            continue;
+        };
+        let value_start = line_start + line.find('=').unwrap_or_default() + 1;
+        let mut end = first_line_end;
+        let mut position = value_start;
+        let mut depth = 0usize;
+        let mut in_string = false;
+        while let Some(c) = resource[position..].chars().next() {
+            match c {
+                // the escaped character may be longer than one byte
+                '\\' if in_string => {
+                    position += 1;
+                    position += resource[position..]
+                        .chars()
+                        .next()
+                        .map_or(0, char::len_utf8);
+                    continue;
+                }
+                '"' if depth > 0 => in_string = !in_string,
+                '{' if !in_string => depth += 1,
+                '}' if !in_string => depth = depth.saturating_sub(1),
+                '\n' => {
+                    let next_end = line_end(resource, position + 1);
+                    let next = &resource[position + 1..next_end];
+                    if depth == 0 && !next.starts_with(' ') && !next.trim().is_empty() {
+                        break;
+                    }
+                    if depth > 0 || !next.trim().is_empty() {
+                        end = next_end;
+                    }
+                    if next_end >= resource.len() {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+            position += c.len_utf8();
+        }
+        entries.push(Entry {
+            id,
+            range: line_start..end,
+            value_start,
+        });
+        line_start = end + 1;
+    }
+    entries
+}
+
+// the lines of a multiline text joined by spaces, the translation is written on one line
+fn join_lines(text: &str) -> String {
+    let lines: Vec<&str> = text
+        .split('\n')
+        .enumerate()
+        .map(|(index, line)| match index {
+            0 => line,
+            _ => line.trim_start(),
+        })
+        .collect();
+    lines.join(" ")
+}
+
+fn line_end(text: &str, start: usize) -> usize {
+    text[start..]
+        .find('\n')
+        .map_or(text.len(), |end| start + end)
+}
+
+// the identifier of a line like `hello-world = Hello` or `-brand = Firefox`
+fn identifier(line: &str) -> Option<&str> {
+    let (id, _) = line.split_once('=')?;
+    let id = id.trim_end();
+    let name = id.strip_prefix('-').unwrap_or(id);
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    valid.then_some(id)
+}
+
+// a run of text with its inline placeables, continuation lines included
+struct Unit {
+    range: Range<usize>,
+    // placeables are true
+    pieces: Vec<(bool, Range<usize>)>,
+}
+
+struct Scanner<'a> {
+    source: &'a str,
+    units: Vec<Unit>,
+}
+
+impl Scanner<'_> {
+    fn pattern(&mut self, start: usize, end: usize) {
+        let mut pieces: Vec<(bool, Range<usize>)> = Vec::new();
+        let mut position = start;
+        while position < end {
+            let rest = &self.source[position..end];
+            let c = rest.chars().next().unwrap_or_default();
+            if c == '\n' {
+                let next = position + 1;
+                let indentation = self.source[next..end].len()
+                    - self.source[next..end].trim_start_matches(' ').len();
+                let prefix = self.line_prefix(next, end);
+                let blank = self.source[next..line_end(self.source, next).min(end)]
+                    .trim()
+                    .is_empty();
+                // a line that goes on with text belongs to the same sentence, variant keys,
+                // attributes and blank lines between paragraphs start a new segment
+                if prefix == indentation && !blank && !pieces.is_empty() {
+                    match pieces.last_mut() {
+                        Some((false, range)) if range.end == position => range.end = next + prefix,
+                        _ => pieces.push((false, position..next + prefix)),
+                    }
+                } else {
+                    self.flush(&mut pieces);
+                }
+                position = next + prefix;
+                continue;
+            }
+            if c == '{' {
+                if let Some(close) = self.matching_brace(position, end) {
+                    match self.select_arrow(position + 1, close) {
+                        // the variants are patterns of their own
+                        Some(arrow) => {
+                            self.flush(&mut pieces);
+                            self.pattern(arrow + 2, close);
+                        }
+                        None => pieces.push((true, position..close + 1)),
+                    }
+                    position = close + 1;
+                    continue;
+                }
+            }
+            match pieces.last_mut() {
+                Some((false, range)) if range.end == position => range.end += c.len_utf8(),
+                _ => pieces.push((false, position..position + c.len_utf8())),
+            }
+            position += c.len_utf8();
+        }
+        self.flush(&mut pieces);
+    }
+
+    fn flush(&mut self, pieces: &mut Vec<(bool, Range<usize>)>) {
+        if let (Some(first), Some(last)) = (pieces.first(), pieces.last()) {
+            self.units.push(Unit {
+                range: first.1.start..last.1.end,
+                pieces: std::mem::take(pieces),
+            });
+        }
+    }
+
+    // length of the indentation and a following variant key `*[other]` or attribute `.title =`
+    fn line_prefix(&self, start: usize, end: usize) -> usize {
+        let line = &self.source[start..line_end(self.source, start).min(end)];
+        let indentation = line.len() - line.trim_start_matches(' ').len();
+        let rest = &line[indentation..];
+        let syntax = if rest.starts_with('[') || rest.starts_with("*[") {
+            rest.find(']').map(|close| close + 1)
+        } else if rest.starts_with('.') {
+            rest.find('=').map(|equals| equals + 1)
+        } else {
+            None
+        };
+        indentation + syntax.unwrap_or_default()
+    }
+
+    fn matching_brace(&self, open: usize, end: usize) -> Option<usize> {
+        let mut depth = 0;
+        let mut in_string = false;
+        let mut escaped = false;
+        for (offset, c) in self.source[open..end].char_indices() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' if in_string => escaped = true,
+                '"' => in_string = !in_string,
+                '{' if !in_string => depth += 1,
+                '}' if !in_string => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(open + offset);
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    // the `->` of a select expression that is not nested deeper
+    fn select_arrow(&self, start: usize, end: usize) -> Option<usize> {
+        let mut depth = 0;
+        let mut in_string = false;
+        let inner = &self.source[start..end];
+        for (offset, c) in inner.char_indices() {
+            match c {
+                '"' => in_string = !in_string,
+                '{' if !in_string => depth += 1,
+                '}' if !in_string => depth -= 1,
+                '-' if !in_string && depth == 0 && inner[offset..].starts_with("->") => {
+                    return Some(start + offset)
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,454 @@
+// Copyright (C) 2022  float3
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{error::Error, fmt};
+
+use crate::{
+    batch::{mask_placeholders, translate_segments, Masked},
+    client::{Client, RequestOptions},
+    lang::{LanguageCode, TargetLanguage},
+};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Entry {
+    // every comment line as written, including the `#,` flags line
+    pub comments: Vec<String>,
+    pub msgctxt: Option<String>,
+    pub msgid: String,
+    pub msgid_plural: Option<String>,
+    // one string for a singular entry, msgstr[n] for a plural one
+    pub msgstr: Vec<String>,
+    // entries commented out with `#~`
+    pub obsolete: bool,
+}
+
+impl Entry {
+    pub fn is_header(&self) -> bool {
+        self.msgid.is_empty() && self.msgctxt.is_none()
+    }
+
+    pub fn is_translated(&self) -> bool {
+        self.msgstr.iter().any(|msgstr| !msgstr.is_empty())
+    }
+
+    pub fn flags(&self) -> Vec<&str> {
+        self.comments
+            .iter()
+            .filter_map(|comment| comment.strip_prefix("#,"))
+            .flat_map(|flags| flags.split(','))
+            .map(str::trim)
+            .filter(|flag| !flag.is_empty())
+            .collect()
+    }
+
+    pub fn is_fuzzy(&self) -> bool {
+        self.flags().contains(&"fuzzy")
+    }
+
+    // adds to an existing flags line or inserts one in front of the `#|` previous strings
+    pub fn set_fuzzy(&mut self) {
+        if self.is_fuzzy() {
+            return;
+        }
+        if let Some(flags) = self
+            .comments
+            .iter_mut()
+            .find(|comment| comment.starts_with("#,"))
+        {
+            flags.push_str(", fuzzy");
+            return;
+        }
+        let position = self
+            .comments
+            .iter()
+            .position(|comment| comment.starts_with("#|"))
+            .unwrap_or(self.comments.len());
+        self.comments.insert(position, "#, fuzzy".to_string());
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Catalog {
+    // the header is the first entry, the one with an empty msgid
+    pub entries: Vec<Entry>,
+}
+
+impl Catalog {
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        let mut entries = Vec::new();
+        let mut entry = Entry::default();
+        let mut has_keyword = false;
+        // the string that continuation lines are appended to
+        let mut last: Option<(&'static str, usize)> = None;
+        for (number, line) in text.trim_start_matches('\u{feff}').lines().enumerate() {
+            let line = line.trim();
+            let (obsolete, line) = match line.strip_prefix("#~") {
+                Some(rest) => (true, rest.trim_start()),
+                None => (false, line),
+            };
+            if line.is_empty() {
+                continue;
+            }
+            if (line.starts_with('#') && !obsolete) || (obsolete && line.starts_with('|')) {
+                // comments start the next entry
+                if has_keyword {
+                    entries.push(std::mem::take(&mut entry));
+                    has_keyword = false;
+                }
+                entry.comments.push(match obsolete {
+                    true => format!("#~{}", line),
+                    false => line.to_string(),
+                });
+                continue;
+            }
+            let invalid = || format!("invalid po line {}: {}", number + 1, line);
+            if line.starts_with('"') {
+                let value = unquote(line).ok_or_else(invalid)?;
+                match last {
+                    Some(("msgctxt", _)) => entry
+                        .msgctxt
+                        .get_or_insert_with(String::new)
+                        .push_str(&value),
+                    Some(("msgid", _)) => entry.msgid.push_str(&value),
+                    Some(("msgid_plural", _)) => entry
+                        .msgid_plural
+                        .get_or_insert_with(String::new)
+                        .push_str(&value),
+                    Some((_, index)) => entry.msgstr[index].push_str(&value),
+                    None => return Result::Err(invalid().into()),
+                }
+                continue;
+            }
+            let (keyword, value) = line.split_once(char::is_whitespace).ok_or_else(invalid)?;
+            let value = unquote(value.trim()).ok_or_else(invalid)?;
+            if (keyword == "msgctxt" || keyword == "msgid")
+                && has_keyword
+                && last.is_some_and(|(last, _)| last.starts_with("msgstr"))
+            {
+                entries.push(std::mem::take(&mut entry));
+            }
+            has_keyword = true;
+            entry.obsolete = obsolete;
+            last = match keyword {
+                "msgctxt" => {
+                    entry.msgctxt = Some(value);
+                    Some(("msgctxt", 0))
+                }
+                "msgid" => {
+                    entry.msgid = value;
+                    Some(("msgid", 0))
+                }
+                "msgid_plural" => {
+                    entry.msgid_plural = Some(value);
+                    Some(("msgid_plural", 0))
+                }
+                "msgstr" => {
+                    entry.msgstr = vec![value];
+                    Some(("msgstr", 0))
+                }
+                _ => {
+                    let index = keyword
+                        .strip_prefix("msgstr[")
+                        .and_then(|rest| rest.strip_suffix(']'))
+                        .and_then(|index| index.parse::<usize>().ok())
+                        .ok_or_else(invalid)?;
+                    if entry.msgstr.len() <= index {
+                        entry.msgstr.resize(index + 1, String::new());
+                    }
+                    entry.msgstr[index] = value;
+                    Some(("msgstr", index))
+                }
+            };
+        }
+        if has_keyword || !entry.comments.is_empty() {
+            entries.push(entry);
+        }
+        Result::Ok(Catalog { entries })
+    }
+
+    pub fn header(&self) -> Option<&Entry> {
+        self.entries.first().filter(|entry| entry.is_header())
+    }
+
+    // a field of the header like "Language" or "Plural-Forms"
+    pub fn header_field(&self, name: &str) -> Option<&str> {
+        self.header()?.msgstr.first()?.lines().find_map(|line| {
+            let (field, value) = line.split_once(':')?;
+            (field.trim().eq_ignore_ascii_case(name)).then(|| value.trim())
+        })
+    }
+
+    // replaces the field or appends it, a missing header is created
+    pub fn set_header_field(&mut self, name: &str, value: &str) {
+        if self.header().is_none() {
+            self.entries.insert(
+                0,
+                Entry {
+                    msgstr: vec![String::new()],
+                    ..Default::default()
+                },
+            );
+        }
+        let header = &mut self.entries[0];
+        if header.msgstr.is_empty() {
+            header.msgstr.push(String::new());
+        }
+        let mut found = false;
+        let mut fields: Vec<String> = header.msgstr[0]
+            .lines()
+            .map(|line| match line.split_once(':') {
+                Some((field, _)) if field.trim().eq_ignore_ascii_case(name) => {
+                    found = true;
+                    format!("{}: {}", name, value)
+                }
+                _ => line.to_string(),
+            })
+            .collect();
+        if !found {
+            fields.push(format!("{}: {}", name, value));
+        }
+        header.msgstr[0] = fields.iter().map(|field| format!("{}\n", field)).collect();
+    }
+}
+
+impl fmt::Display for Catalog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, entry) in self.entries.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            for comment in &entry.comments {
+                writeln!(f, "{}", comment)?;
+            }
+            let prefix = if entry.obsolete { "#~ " } else { "" };
+            if let Some(msgctxt) = &entry.msgctxt {
+                write_string(f, prefix, "msgctxt", msgctxt)?;
+            }
+            write_string(f, prefix, "msgid", &entry.msgid)?;
+            match &entry.msgid_plural {
+                Some(msgid_plural) => {
+                    write_string(f, prefix, "msgid_plural", msgid_plural)?;
+                    for (index, msgstr) in entry.msgstr.iter().enumerate() {
+                        write_string(f, prefix, &format!("msgstr[{}]", index), msgstr)?;
+                    }
+                }
+                None => write_string(
+                    f,
+                    prefix,
+                    "msgstr",
+                    entry.msgstr.first().map(String::as_str).unwrap_or_default(),
+                )?,
+            }
+        }
+        Result::Ok(())
+    }
+}
+
+// strings with a line break inside are written one line per line as msgmerge does
+fn write_string(
+    f: &mut fmt::Formatter<'_>,
+    prefix: &str,
+    keyword: &str,
+    value: &str,
+) -> fmt::Result {
+    let lines: Vec<&str> = value.split_inclusive('\n').collect();
+    if lines.len() < 2 {
+        return writeln!(f, "{}{} \"{}\"", prefix, keyword, escape(value));
+    }
+    writeln!(f, "{}{} \"\"", prefix, keyword)?;
+    for line in lines {
+        writeln!(f, "{}\"{}\"", prefix, escape(line))?;
+    }
+    Result::Ok(())
+}
+
+fn unquote(value: &str) -> Option<String> {
+    let inner = value.strip_prefix('"')?.strip_suffix('"')?;
+    let mut unescaped = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        unescaped.push(match chars.next()? {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            'a' => '\u{7}',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'v' => '\u{b}',
+            other => other,
+        });
+    }
+    Some(unescaped)
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// gettext plural forms of a language, the number of forms, the plural expression and
+// the index of the form used for exactly one
+pub fn plural_forms(language: LanguageCode) -> (usize, &'static str, Option<usize>) {
+    use LanguageCode::*;
+    match language {
+        _ if language.info().plural_categories.len() == 1 => (1, "0", None),
+        fr | pt | am | ti | ak | ln | mg | hy => (2, "(n > 1)", Some(0)),
+        is => (2, "(n%10!=1 || n%100==11)", Some(0)),
+        mk => (2, "(n%10==1 && n%100!=11) ? 0 : 1", Some(0)),
+        ru | uk | be | sr | hr | bs => (
+            3,
+            "(n%10==1 && n%100!=11 ? 0 : n%10>=2 && n%10<=4 && (n%100<10 || n%100>=20) ? 1 : 2)",
+            Some(0),
+        ),
+        pl => (
+            3,
+            "(n==1 ? 0 : n%10>=2 && n%10<=4 && (n%100<10 || n%100>=20) ? 1 : 2)",
+            Some(0),
+        ),
+        cs | sk => (3, "(n==1) ? 0 : (n>=2 && n<=4) ? 1 : 2", Some(0)),
+        lt => (
+            3,
+            "(n%10==1 && n%100!=11 ? 0 : n%10>=2 && (n%100<10 || n%100>=20) ? 1 : 2)",
+            Some(0),
+        ),
+        lv => (3, "(n%10==1 && n%100!=11 ? 0 : n != 0 ? 1 : 2)", Some(0)),
+        ro => (
+            3,
+            "(n==1 ? 0 : (n==0 || (n%100 > 0 && n%100 < 20)) ? 1 : 2)",
+            Some(0),
+        ),
+        sl => (
+            4,
+            "(n%100==1 ? 0 : n%100==2 ? 1 : n%100==3 || n%100==4 ? 2 : 3)",
+            Some(0),
+        ),
+        cy => (
+            4,
+            "(n==1) ? 0 : (n==2) ? 1 : (n != 8 && n != 11) ? 2 : 3",
+            Some(0),
+        ),
+        gd => (
+            4,
+            "(n==1 || n==11) ? 0 : (n==2 || n==12) ? 1 : (n > 2 && n < 20) ? 2 : 3",
+            Some(0),
+        ),
+        mt => (
+            4,
+            "(n==1 ? 0 : n==0 || (n%100>1 && n%100<11) ? 1 : (n%100>10 && n%100<20) ? 2 : 3)",
+            Some(0),
+        ),
+        ga => (5, "(n==1 ? 0 : n==2 ? 1 : n<7 ? 2 : n<11 ? 3 : 4)", Some(0)),
+        ar => (
+            6,
+            "(n==0 ? 0 : n==1 ? 1 : n==2 ? 2 : n%100>=3 && n%100<=10 ? 3 : n%100>=11 ? 4 : 5)",
+            Some(1),
+        ),
+        _ => (2, "(n != 1)", Some(0)),
+    }
+}
+
+// the locale name gettext uses for the Language header
+pub fn gettext_language(language: LanguageCode) -> String {
+    language.bcp_47().replace('-', "_")
+}
+
+// fills every untranslated entry of a .po or .pot catalog and marks it fuzzy
+// plural entries get one msgstr per plural form of the target language, the form used for
+// exactly one gets the translated msgid and all others the translated msgid_plural
+// the Language and Plural-Forms header fields are set for the target
+pub fn translate_catalog(
+    client: &Client,
+    catalog: &Catalog,
+    source_language: LanguageCode,
+    target_language: TargetLanguage,
+    options: &RequestOptions,
+) -> Result<Catalog, Box<dyn Error>> {
+    let mut catalog = catalog.clone();
+    let (forms, expression, singular) = plural_forms(target_language.language_code());
+    catalog.set_header_field(
+        "Language",
+        &gettext_language(target_language.language_code()),
+    );
+    catalog.set_header_field(
+        "Plural-Forms",
+        &format!("nplurals={}; plural={};", forms, expression),
+    );
+
+    let pending: Vec<usize> = catalog
+        .entries
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| !entry.is_header() && !entry.obsolete && !entry.is_translated())
+        .map(|(index, _)| index)
+        .collect();
+    // c-format and python-format arguments like %d, %(name)s or {name} are never sent
+    let masked: Vec<(usize, Masked, Option<Masked>)> = pending
+        .into_iter()
+        .map(|index| {
+            let entry = &catalog.entries[index];
+            let plural = entry.msgid_plural.as_deref().map(mask_placeholders);
+            (index, mask_placeholders(&entry.msgid), plural)
+        })
+        .collect();
+    let segments: Vec<&str> = masked
+        .iter()
+        .flat_map(|(_, msgid, plural)| std::iter::once(msgid).chain(plural))
+        .filter(|masked| masked.has_text())
+        .map(Masked::text)
+        .collect();
+    let mut translations =
+        translate_segments(client, &segments, source_language, target_language, options)?
+            .into_iter();
+    let mut unmask = |masked: &Masked| match masked.has_text() {
+        true => masked.unmask(&translations.next().unwrap_or_default()),
+        false => masked.unmask(masked.text()),
+    };
+
+    for (index, msgid, plural) in masked {
+        let entry = &mut catalog.entries[index];
+        let translation = unmask(&msgid);
+        entry.msgstr = match plural {
+            Some(plural) => {
+                let plural = unmask(&plural);
+                (0..forms)
+                    .map(|form| {
+                        if Some(form) == singular {
+                            translation.clone()
+                        } else {
+                            plural.clone()
+                        }
+                    })
+                    .collect()
+            }
+            None => vec![translation],
+        };
+        entry.set_fuzzy();
+    }
+    Result::Ok(catalog)
+}
@@ -0,0 +1,135 @@
+// Copyright (C) 2022  float3
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    error::Error,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+
+pub(crate) const DEFAULT_REFERER: &str = "https://translate.google.com/";
+pub(crate) const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/141.0.0.0 Safari/537.36";
+
+// headers a real browser sends together, so they have to be rotated as a unit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderSet {
+    pub user_agent: String,
+    pub accept_language: String,
+    // client hints, only sent by chromium based browsers
+    pub sec_ch_ua: Option<String>,
+    pub sec_ch_ua_mobile: Option<String>,
+    pub sec_ch_ua_platform: Option<String>,
+}
+
+impl HeaderSet {
+    // current desktop browsers as of 2026-10
+    pub fn browsers() -> Vec<HeaderSet> {
+        let chromium = |user_agent: &str, brand: &str, platform: &str| HeaderSet {
+            user_agent: user_agent.to_string(),
+            accept_language: "en-US,en;q=0.9".to_string(),
+            sec_ch_ua: Some(brand.to_string()),
+            sec_ch_ua_mobile: Some("?0".to_string()),
+            sec_ch_ua_platform: Some(platform.to_string()),
+        };
+        let other = |user_agent: &str, accept_language: &str| HeaderSet {
+            user_agent: user_agent.to_string(),
+            accept_language: accept_language.to_string(),
+            sec_ch_ua: None,
+            sec_ch_ua_mobile: None,
+            sec_ch_ua_platform: None,
+        };
+        vec![
+            chromium(
+                DEFAULT_USER_AGENT,
+                "\"Google Chrome\";v=\"141\", \"Not?A_Brand\";v=\"8\", \"Chromium\";v=\"141\"",
+                "\"Windows\"",
+            ),
+            chromium(
+                "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/141.0.0.0 Safari/537.36",
+                "\"Google Chrome\";v=\"141\", \"Not?A_Brand\";v=\"8\", \"Chromium\";v=\"141\"",
+                "\"macOS\"",
+            ),
+            chromium(
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/141.0.0.0 Safari/537.36 Edg/141.0.0.0",
+                "\"Microsoft Edge\";v=\"141\", \"Not?A_Brand\";v=\"8\", \"Chromium\";v=\"141\"",
+                "\"Windows\"",
+            ),
+            chromium(
+                "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/141.0.0.0 Safari/537.36",
+                "\"Google Chrome\";v=\"141\", \"Not?A_Brand\";v=\"8\", \"Chromium\";v=\"141\"",
+                "\"Linux\"",
+            ),
+            other(
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:144.0) Gecko/20100101 Firefox/144.0",
+                "en-US,en;q=0.5",
+            ),
+            other(
+                "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/26.0 Safari/605.1.15",
+                "en-US,en;q=0.9",
+            ),
+        ]
+    }
+
+    pub(crate) fn to_header_map(&self) -> Result<HeaderMap, Box<dyn Error>> {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            reqwest::header::USER_AGENT,
+            HeaderValue::from_str(&self.user_agent)?,
+        );
+        headers.insert(
+            reqwest::header::ACCEPT_LANGUAGE,
+            HeaderValue::from_str(&self.accept_language)?,
+        );
+        let hints = [
+            ("sec-ch-ua", &self.sec_ch_ua),
+            ("sec-ch-ua-mobile", &self.sec_ch_ua_mobile),
+            ("sec-ch-ua-platform", &self.sec_ch_ua_platform),
+        ];
+        for (name, value) in hints {
+            if let Some(value) = value {
+                headers.insert(HeaderName::from_static(name), HeaderValue::from_str(value)?);
+            }
+        }
+        Result::Ok(headers)
+    }
+}
+
+// the validated header sets of a client, handed out round robin
+#[derive(Debug)]
+pub(crate) struct HeaderRotation {
+    sets: Vec<HeaderMap>,
+    next: AtomicUsize,
+}
+
+impl HeaderRotation {
+    pub(crate) fn new(sets: &[HeaderSet]) -> Result<Self, Box<dyn Error>> {
+        Result::Ok(HeaderRotation {
+            sets: sets
+                .iter()
+                .map(HeaderSet::to_header_map)
+                .collect::<Result<_, _>>()?,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    pub(crate) fn next(&self) -> Option<&HeaderMap> {
+        if self.sets.is_empty() {
+            return None;
+        }
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.sets.len();
+        Some(&self.sets[index])
+    }
+}
@@ -0,0 +1,410 @@
+// Copyright (C) 2022  float3
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{error::Error, rc::Rc};
+
+use html5ever::{
+    local_name, namespace_url, ns, parse_document, parse_fragment,
+    serialize::{serialize, SerializeOpts, TraversalScope},
+    tendril::TendrilSink,
+    Attribute, LocalName, QualName,
+};
+use markup5ever_rcdom::{Handle, NodeData, RcDom, SerializableHandle};
+
+use crate::{
+    batch::{translate_segments, Masked},
+    client::{Client, RequestOptions},
+    lang::{LanguageCode, TargetLanguage},
+};
+
+// elements whose content is never translated
+const SKIPPED_ELEMENTS: [&str; 7] = ["script", "style", "code", "pre", "kbd", "samp", "textarea"];
+// attributes that are translated along with the text nodes
+const TRANSLATED_ATTRIBUTES: [&str; 3] = ["alt", "title", "placeholder"];
+// elements that flow with the text around them, their tags become placeholders in its sentence
+const INLINE_ELEMENTS: [&str; 30] = [
+    "a", "abbr", "b", "bdi", "bdo", "br", "cite", "code", "data", "del", "dfn", "em", "font", "i",
+    "img", "ins", "kbd", "mark", "q", "s", "samp", "small", "span", "strong", "sub", "sup", "time",
+    "u", "var", "wbr",
+];
+
+// translates the text and the alt, title and placeholder attributes of a document or fragment
+// text is sent one block at a time with its inline elements as placeholders, so a sentence
+// like `Click <a>here</a> to continue` is translated as a whole and its words can move
+// elements with translate="no" or class="notranslate" and code, pre, script and style are left alone
+// everything else, including the markup itself, comes back as it was parsed
+pub fn translate_html(
+    client: &Client,
+    html: &str,
+    source_language: LanguageCode,
+    target_language: TargetLanguage,
+    options: &RequestOptions,
+) -> Result<String, Box<dyn Error>> {
+    let start = html
+        .trim_start()
+        .get(..9)
+        .unwrap_or_default()
+        .to_lowercase();
+    let is_document = start.starts_with("<!doctype") || start.starts_with("<html");
+    let dom = if is_document {
+        parse_document(RcDom::default(), Default::default()).one(html)
+    } else {
+        parse_fragment(
+            RcDom::default(),
+            Default::default(),
+            QualName::new(None, ns!(html), local_name!("body")),
+            Vec::new(),
+        )
+        .one(html)
+    };
+
+    let mut slots = Vec::new();
+    collect(&dom.document, true, &mut slots);
+    let pending: Vec<(Slot, Masked)> = slots
+        .into_iter()
+        .map(|slot| Result::Ok((slot.mask()?, slot)))
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?
+        .into_iter()
+        .filter(|(masked, _)| masked.has_text())
+        .map(|(masked, slot)| (slot, masked))
+        .collect();
+    let segments: Vec<&str> = pending.iter().map(|(_, masked)| masked.text()).collect();
+    let translations =
+        translate_segments(client, &segments, source_language, target_language, options)?;
+    // attributes go first, the tags of a block are rendered again afterwards and so keep the
+    // translated attributes of its inline elements
+    for ((slot, masked), translation) in pending.iter().zip(&translations) {
+        if let Slot::Attribute(..) = slot {
+            slot.set_attribute(masked.unmask(translation));
+        }
+    }
+    for ((slot, _), translation) in pending.iter().zip(&translations) {
+        if let Slot::Block { .. } = slot {
+            slot.set_block(translation)?;
+        }
+    }
+
+    // a fragment is parsed into an html element, only its children are the original input
+    let root = if is_document {
+        dom.document.clone()
+    } else {
+        let children = dom.document.children.borrow();
+        children
+            .first()
+            .cloned()
+            .ok_or("html fragment has no root")?
+    };
+    to_html(&root, TraversalScope::ChildrenOnly(None))
+}
+
+enum Slot {
+    // sibling text nodes and inline elements that are translated together
+    Block {
+        parent: Handle,
+        nodes: Vec<Handle>,
+        items: Vec<Item>,
+    },
+    Attribute(Handle, LocalName),
+}
+
+enum Item {
+    Text(Handle),
+    Open(Handle),
+    Close(Handle),
+    // an element or comment that is kept as it is
+    Whole(Handle),
+}
+
+impl Slot {
+    fn mask(&self) -> Result<Masked, Box<dyn Error>> {
+        let mut masked = Masked::default();
+        match self {
+            Slot::Block { items, .. } => {
+                for item in items {
+                    match item {
+                        Item::Text(node) => masked.push_text_masking_placeholders(
+                            &collapse_whitespace(&text(node)),
+                            escape_text,
+                        ),
+                        Item::Open(node) => masked.push_placeholder(&open_tag(node)),
+                        Item::Close(node) => masked.push_placeholder(&close_tag(node)),
+                        Item::Whole(node) => {
+                            masked.push_placeholder(&to_html(node, TraversalScope::IncludeNode)?)
+                        }
+                    }
+                }
+            }
+            Slot::Attribute(node, name) => {
+                if let NodeData::Element { attrs, .. } = &node.data {
+                    let value = attrs
+                        .borrow()
+                        .iter()
+                        .find(|attribute| attribute.name.local == *name)
+                        .map(|attribute| attribute.value.to_string())
+                        .unwrap_or_default();
+                    masked.push_text_masking_placeholders(&value, str::to_string);
+                }
+            }
+        }
+        Result::Ok(masked)
+    }
+
+    fn set_attribute(&self, value: String) {
+        if let Slot::Attribute(node, name) = self {
+            if let NodeData::Element { attrs, .. } = &node.data {
+                for attribute in attrs.borrow_mut().iter_mut() {
+                    if attribute.name.local == *name {
+                        attribute.value = value.clone().into();
+                    }
+                }
+            }
+        }
+    }
+
+    fn set_block(&self, translation: &str) -> Result<(), Box<dyn Error>> {
+        let Slot::Block {
+            parent,
+            nodes,
+            items,
+        } = self
+        else {
+            return Result::Ok(());
+        };
+        // plain text is set in place
+        if let [Item::Text(node)] = items.as_slice() {
+            if let NodeData::Text { contents } = &node.data {
+                let mut masked = Masked::default();
+                masked.push_text_masking_placeholders(
+                    &collapse_whitespace(&text(node)),
+                    str::to_string,
+                );
+                *contents.borrow_mut() = masked.unmask(translation).into();
+            }
+            return Result::Ok(());
+        }
+        // otherwise the translated markup is parsed where the block was and replaces it
+        let html = self.mask()?.unmask(&escape_text(translation));
+        let context = match &parent.data {
+            NodeData::Element { name, .. } => name.clone(),
+            _ => QualName::new(None, ns!(html), local_name!("body")),
+        };
+        let dom =
+            parse_fragment(RcDom::default(), Default::default(), context, Vec::new()).one(html);
+        let root = dom.document.children.borrow().first().cloned();
+        let replacement: Vec<Handle> = root.map(|root| root.children.take()).unwrap_or_default();
+        for node in &replacement {
+            node.parent.set(Some(Rc::downgrade(parent)));
+        }
+        let mut children = parent.children.borrow_mut();
+        if let Some(start) = children
+            .iter()
+            .position(|child| Rc::ptr_eq(child, &nodes[0]))
+        {
+            children.splice(start..start + nodes.len(), replacement);
+        }
+        Result::Ok(())
+    }
+}
+
+// walks the tree in document order and groups the children of every element into blocks
+fn collect(node: &Handle, translate: bool, slots: &mut Vec<Slot>) {
+    let mut translate = translate;
+    if let NodeData::Element {
+        attrs,
+        template_contents,
+        ..
+    } = &node.data
+    {
+        translate = translates(node, translate);
+        if translate {
+            attribute_slots(node, &attrs.borrow(), slots);
+        }
+        if let Some(contents) = template_contents.borrow().as_ref() {
+            collect(contents, translate, slots);
+        }
+    }
+    let children = node.children.borrow();
+    let mut index = 0;
+    while index < children.len() {
+        let length = children[index..]
+            .iter()
+            .take_while(|child| is_inline(child))
+            .count();
+        if length == 0 {
+            collect(&children[index], translate, slots);
+            index += 1;
+            continue;
+        }
+        let nodes = children[index..index + length].to_vec();
+        if translate {
+            let mut items = Vec::new();
+            for node in &nodes {
+                inline_items(node, slots, &mut items);
+            }
+            slots.push(Slot::Block {
+                parent: node.clone(),
+                nodes,
+                items,
+            });
+        } else {
+            // translate="yes" can turn translation back on further down
+            for node in &nodes {
+                collect(node, false, slots);
+            }
+        }
+        index += length;
+    }
+}
+
+fn inline_items(node: &Handle, slots: &mut Vec<Slot>, items: &mut Vec<Item>) {
+    match &node.data {
+        NodeData::Text { .. } => items.push(Item::Text(node.clone())),
+        NodeData::Element { attrs, .. }
+            if translates(node, true) && !node.children.borrow().is_empty() =>
+        {
+            attribute_slots(node, &attrs.borrow(), slots);
+            items.push(Item::Open(node.clone()));
+            for child in node.children.borrow().iter() {
+                inline_items(child, slots, items);
+            }
+            items.push(Item::Close(node.clone()));
+        }
+        NodeData::Element { attrs, .. } => {
+            if translates(node, true) {
+                attribute_slots(node, &attrs.borrow(), slots);
+            }
+            items.push(Item::Whole(node.clone()));
+        }
+        _ => items.push(Item::Whole(node.clone())),
+    }
+}
+
+// text nodes, comments and inline elements that hold nothing else
+fn is_inline(node: &Handle) -> bool {
+    match &node.data {
+        NodeData::Text { .. } | NodeData::Comment { .. } => true,
+        NodeData::Element { name, .. } => {
+            INLINE_ELEMENTS.contains(&&*name.local) && node.children.borrow().iter().all(is_inline)
+        }
+        _ => false,
+    }
+}
+
+// whether the content of an element is translated, translate="yes" re-enables translation
+// inside a skipped element
+fn translates(node: &Handle, translate: bool) -> bool {
+    let NodeData::Element { name, attrs, .. } = &node.data else {
+        return translate;
+    };
+    let attrs = attrs.borrow();
+    let attribute = |wanted: &str| {
+        attrs
+            .iter()
+            .find(|attribute| &*attribute.name.local == wanted)
+            .map(|attribute| attribute.value.to_string())
+    };
+    let mut translate = translate;
+    match attribute("translate").as_deref().map(str::to_lowercase) {
+        Some(value) if value == "no" => translate = false,
+        Some(value) if value == "yes" || value.is_empty() => translate = true,
+        _ => {}
+    }
+    if attribute("class")
+        .is_some_and(|class| class.split_whitespace().any(|name| name == "notranslate"))
+        || SKIPPED_ELEMENTS.contains(&&*name.local)
+    {
+        translate = false;
+    }
+    translate
+}
+
+fn attribute_slots(node: &Handle, attrs: &[Attribute], slots: &mut Vec<Slot>) {
+    for wanted in TRANSLATED_ATTRIBUTES {
+        if attrs
+            .iter()
+            .any(|attribute| &*attribute.name.local == wanted && !attribute.value.trim().is_empty())
+        {
+            slots.push(Slot::Attribute(node.clone(), LocalName::from(wanted)));
+        }
+    }
+}
+
+fn text(node: &Handle) -> String {
+    match &node.data {
+        NodeData::Text { contents } => contents.borrow().to_string(),
+        _ => String::new(),
+    }
+}
+
+fn open_tag(node: &Handle) -> String {
+    let NodeData::Element { name, attrs, .. } = &node.data else {
+        return String::new();
+    };
+    let mut tag = format!("<{}", name.local);
+    for attribute in attrs.borrow().iter() {
+        let value = attribute
+            .value
+            .replace('&', "&amp;")
+            .replace('\u{a0}', "&nbsp;")
+            .replace('"', "&quot;");
+        tag.push_str(&format!(" {}=\"{}\"", attribute.name.local, value));
+    }
+    tag.push('>');
+    tag
+}
+
+fn close_tag(node: &Handle) -> String {
+    match &node.data {
+        NodeData::Element { name, .. } => format!("</{}>", name.local),
+        _ => String::new(),
+    }
+}
+
+// text as html5ever serializes it
+fn escape_text(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('\u{a0}', "&nbsp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn to_html(node: &Handle, traversal_scope: TraversalScope) -> Result<String, Box<dyn Error>> {
+    let mut output = Vec::new();
+    serialize(
+        &mut output,
+        &SerializableHandle::from(node.clone()),
+        SerializeOpts {
+            traversal_scope,
+            ..Default::default()
+        },
+    )?;
+    Result::Ok(String::from_utf8(output)?)
+}
+
+// runs of whitespace inside a text node render as a single space, the edges are kept for the layout
+fn collapse_whitespace(text: &str) -> String {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return text.to_string();
+    }
+    let start = text.len() - text.trim_start().len();
+    let end = text.trim_end().len();
+    format!(
+        "{}{}{}",
+        &text[..start],
+        trimmed.split_whitespace().collect::<Vec<_>>().join(" "),
+        &text[end..]
+    )
+}
@@ -13,700 +13,411 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-// all languages listed in view-source:https://translate.google.com as of 2022-11-15
+use std::{error::Error, fmt};
+
+// generates LanguageCode, LanguageName and every per language table from one list, so a
+// language can not be missing from one of them
+// a row is the code, its iso 639 name, the english name, the native name, the ISO 15924
+// script and the CLDR plural categories, the first row is the default
+macro_rules! languages {
+    (
+        $default:ident => ($default_iso:literal, $default_name:ident, $default_native:literal, $default_script:literal, $default_plurals:ident),
+        $($code:ident => ($iso:literal, $name:ident, $native:literal, $script:literal, $plurals:ident),)*
+    ) => {
+        #[allow(non_camel_case_types)]
+        #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub enum LanguageCode {
+            #[default]
+            $default,
+            $($code,)*
+        }
+
+        #[allow(non_camel_case_types)]
+        #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub enum LanguageName {
+            #[default]
+            $default_name,
+            $($name,)*
+        }
+
+        impl LanguageCode {
+            // every variant in declaration order
+            pub const ALL: &'static [LanguageCode] = &[LanguageCode::$default, $(LanguageCode::$code,)*];
+
+            pub fn iso_639(&self) -> &'static str {
+                match self {
+                    LanguageCode::$default => $default_iso,
+                    $(LanguageCode::$code => $iso,)*
+                }
+            }
+
+            pub fn from_iso_639(s: &str) -> Option<Self> {
+                match s {
+                    $default_iso => Some(LanguageCode::$default),
+                    $($iso => Some(LanguageCode::$code),)*
+                    _ => None,
+                }
+            }
 
-#[allow(non_camel_case_types)]
-#[derive(Debug, Default, Clone, Copy)]
-pub enum LanguageCode {
-    #[default]
-    auto,
-    af,
-    sq,
-    am,
-    ar,
-    hy,
-    r#as,
-    ay,
-    az,
-    bm,
-    eu,
-    be,
-    bn,
-    bho,
-    bs,
-    bg,
-    ca,
-    ceb,
-    ny,
-    zh_CN,
-    zh_TW,
-    co,
-    hr,
-    cs,
-    da,
-    dv,
-    doi,
-    nl,
-    en,
-    eo,
-    et,
-    ee,
-    tl,
-    fi,
-    fr,
-    fy,
-    gl,
-    ka,
-    de,
-    el,
-    gn,
-    gu,
-    ht,
-    ha,
-    haw,
-    iw,
-    hi,
-    hmn,
-    hu,
-    is,
-    ig,
-    ilo,
-    id,
-    ga,
-    it,
-    ja,
-    jw,
-    kn,
-    kk,
-    km,
-    rw,
-    gom,
-    ko,
-    kri,
-    ku,
-    ckb,
-    ky,
-    lo,
-    la,
-    lv,
-    ln,
-    lt,
-    lg,
-    lb,
-    mk,
-    mai,
-    mg,
-    ms,
-    ml,
-    mt,
-    mi,
-    mr,
-    mni_Mtei,
-    lus,
-    mn,
-    my,
-    ne,
-    no,
-    or,
-    om,
-    ps,
-    fa,
-    pl,
-    pt,
-    pa,
-    qu,
-    ro,
-    ru,
-    sm,
-    sa,
-    gd,
-    nso,
-    sr,
-    st,
-    sn,
-    sd,
-    si,
-    sk,
-    sl,
-    so,
-    es,
-    su,
-    sw,
-    sv,
-    tg,
-    ta,
-    tt,
-    te,
-    th,
-    ti,
-    ts,
-    tr,
-    tk,
-    ak,
-    uk,
-    ur,
-    ug,
-    uz,
-    vi,
-    cy,
-    xh,
-    yi,
-    yo,
-    zu,
+            pub fn to_language_name(&self) -> &'static LanguageName {
+                match self {
+                    LanguageCode::$default => &LanguageName::$default_name,
+                    $(LanguageCode::$code => &LanguageName::$name,)*
+                }
+            }
+
+            pub fn native_name(&self) -> &'static str {
+                match self {
+                    LanguageCode::$default => $default_native,
+                    $(LanguageCode::$code => $native,)*
+                }
+            }
+
+            pub fn info(&self) -> LanguageInfo {
+                let (script, plural_categories) = match self {
+                    LanguageCode::$default => ($default_script, $default_plurals),
+                    $(LanguageCode::$code => ($script, $plurals),)*
+                };
+                LanguageInfo {
+                    code: *self,
+                    script,
+                    direction: Direction::from_script(script),
+                    plural_categories,
+                }
+            }
+        }
+
+        // the target languages by the name of their code, like TargetLanguage::de
+        #[allow(non_upper_case_globals)]
+        impl TargetLanguage {
+            $(pub const $code: TargetLanguage = TargetLanguage(LanguageCode::$code);)*
+        }
+    };
+}
+
+// all languages listed in view-source:https://translate.google.com as of 2022-11-15
+languages! {
+    auto => ("auto", Detect_language, "Detect language", "Zzzz", OTHER),
+    af => ("af", Afrikaans, "Afrikaans", "Latn", ONE_OTHER),
+    sq => ("sq", Albanian, "Shqip", "Latn", ONE_OTHER),
+    am => ("am", Amharic, "አማርኛ", "Ethi", ONE_OTHER),
+    ar => ("ar", Arabic, "العربية", "Arab", ZERO_ONE_TWO_FEW_MANY_OTHER),
+    hy => ("hy", Armenian, "Հայերեն", "Armn", ONE_OTHER),
+    r#as => ("as", Assamese, "অসমীয়া", "Beng", ONE_OTHER),
+    ay => ("ay", Aymara, "Aymar aru", "Latn", ONE_OTHER),
+    az => ("az", Azerbaijani, "Azərbaycan", "Latn", ONE_OTHER),
+    bm => ("bm", Bambara, "Bamanankan", "Latn", OTHER),
+    eu => ("eu", Basque, "Euskara", "Latn", ONE_OTHER),
+    be => ("be", Belarusian, "Беларуская", "Cyrl", ONE_FEW_MANY_OTHER),
+    bn => ("bn", Bengali, "বাংলা", "Beng", ONE_OTHER),
+    bho => ("bho", Bhojpuri, "भोजपुरी", "Deva", ONE_OTHER),
+    bs => ("bs", Bosnian, "Bosanski", "Latn", ONE_FEW_OTHER),
+    bg => ("bg", Bulgarian, "Български", "Cyrl", ONE_OTHER),
+    ca => ("ca", Catalan, "Català", "Latn", ONE_MANY_OTHER),
+    ceb => ("ceb", Cebuano, "Sinugboanon", "Latn", ONE_OTHER),
+    ny => ("ny", Chichewa, "Chicheŵa", "Latn", ONE_OTHER),
+    zh_CN => ("zh-CN", Chinese_Simplified, "中文（简体）", "Hans", OTHER),
+    zh_TW => ("zh-TW", Chinese_Traditional, "中文（繁體）", "Hant", OTHER),
+    co => ("co", Corsican, "Corsu", "Latn", ONE_OTHER),
+    hr => ("hr", Croatian, "Hrvatski", "Latn", ONE_FEW_OTHER),
+    cs => ("cs", Czech, "Čeština", "Latn", ONE_FEW_MANY_OTHER),
+    da => ("da", Danish, "Dansk", "Latn", ONE_OTHER),
+    dv => ("dv", Dhivehi, "ދިވެހި", "Thaa", ONE_OTHER),
+    doi => ("doi", Dogri, "डोगरी", "Deva", ONE_OTHER),
+    nl => ("nl", Dutch, "Nederlands", "Latn", ONE_OTHER),
+    en => ("en", English, "English", "Latn", ONE_OTHER),
+    eo => ("eo", Esperanto, "Esperanto", "Latn", ONE_OTHER),
+    et => ("et", Estonian, "Eesti", "Latn", ONE_OTHER),
+    ee => ("ee", Ewe, "Eʋegbe", "Latn", ONE_OTHER),
+    tl => ("tl", Filipino, "Filipino", "Latn", ONE_OTHER),
+    fi => ("fi", Finnish, "Suomi", "Latn", ONE_OTHER),
+    fr => ("fr", French, "Français", "Latn", ONE_MANY_OTHER),
+    fy => ("fy", Frisian, "Frysk", "Latn", ONE_OTHER),
+    gl => ("gl", Galician, "Galego", "Latn", ONE_OTHER),
+    ka => ("ka", Georgian, "ქართული", "Geor", ONE_OTHER),
+    de => ("de", German, "Deutsch", "Latn", ONE_OTHER),
+    el => ("el", Greek, "Ελληνικά", "Grek", ONE_OTHER),
+    gn => ("gn", Guarani, "Avañe'ẽ", "Latn", ONE_OTHER),
+    gu => ("gu", Gujarati, "ગુજરાતી", "Gujr", ONE_OTHER),
+    ht => ("ht", Haitian_Creole, "Kreyòl ayisyen", "Latn", ONE_OTHER),
+    ha => ("ha", Hausa, "Hausa", "Latn", ONE_OTHER),
+    haw => ("haw", Hawaiian, "ʻŌlelo Hawaiʻi", "Latn", ONE_OTHER),
+    iw => ("iw", Hebrew, "עברית", "Hebr", ONE_TWO_OTHER),
+    hi => ("hi", Hindi, "हिन्दी", "Deva", ONE_OTHER),
+    hmn => ("hmn", Hmong, "Hmoob", "Latn", ONE_OTHER),
+    hu => ("hu", Hungarian, "Magyar", "Latn", ONE_OTHER),
+    is => ("is", Icelandic, "Íslenska", "Latn", ONE_OTHER),
+    ig => ("ig", Igbo, "Asụsụ Igbo", "Latn", OTHER),
+    ilo => ("ilo", Ilocano, "Ilokano", "Latn", ONE_OTHER),
+    id => ("id", Indonesian, "Bahasa Indonesia", "Latn", OTHER),
+    ga => ("ga", Irish, "Gaeilge", "Latn", ONE_TWO_FEW_MANY_OTHER),
+    it => ("it", Italian, "Italiano", "Latn", ONE_MANY_OTHER),
+    ja => ("ja", Japanese, "日本語", "Jpan", OTHER),
+    jw => ("jw", Javanese, "Basa Jawa", "Latn", OTHER),
+    kn => ("kn", Kannada, "ಕನ್ನಡ", "Knda", ONE_OTHER),
+    kk => ("kk", Kazakh, "Қазақ тілі", "Cyrl", ONE_OTHER),
+    km => ("km", Khmer, "ខ្មែរ", "Khmr", OTHER),
+    rw => ("rw", Kinyarwanda, "Ikinyarwanda", "Latn", ONE_OTHER),
+    gom => ("gom", Konkani, "कोंकणी", "Deva", ONE_OTHER),
+    ko => ("ko", Korean, "한국어", "Kore", OTHER),
+    kri => ("kri", Krio, "Krio", "Latn", ONE_OTHER),
+    ku => ("ku", Kurdish_Kurmanji, "Kurdî (Kurmancî)", "Latn", ONE_OTHER),
+    ckb => ("ckb", Kurdish_Sorani, "کوردی (سۆرانی)", "Arab", ONE_OTHER),
+    ky => ("ky", Kyrgyz, "Кыргызча", "Cyrl", ONE_OTHER),
+    lo => ("lo", Lao, "ລາວ", "Laoo", OTHER),
+    la => ("la", Latin, "Latina", "Latn", ONE_OTHER),
+    lv => ("lv", Latvian, "Latviešu", "Latn", ZERO_ONE_OTHER),
+    ln => ("ln", Lingala, "Lingála", "Latn", ONE_OTHER),
+    lt => ("lt", Lithuanian, "Lietuvių", "Latn", ONE_FEW_MANY_OTHER),
+    lg => ("lg", Luganda, "Luganda", "Latn", ONE_OTHER),
+    lb => ("lb", Luxembourgish, "Lëtzebuergesch", "Latn", ONE_OTHER),
+    mk => ("mk", Macedonian, "Македонски", "Cyrl", ONE_OTHER),
+    mai => ("mai", Maithili, "मैथिली", "Deva", ONE_OTHER),
+    mg => ("mg", Malagasy, "Malagasy", "Latn", ONE_OTHER),
+    ms => ("ms", Malay, "Bahasa Melayu", "Latn", OTHER),
+    ml => ("ml", Malayalam, "മലയാളം", "Mlym", ONE_OTHER),
+    mt => ("mt", Maltese, "Malti", "Latn", ONE_TWO_FEW_MANY_OTHER),
+    mi => ("mi", Maori, "Te reo Māori", "Latn", ONE_OTHER),
+    mr => ("mr", Marathi, "मराठी", "Deva", ONE_OTHER),
+    mni_Mtei => ("mni-Mtei", Meiteilon_Manipuri, "ꯃꯤꯇꯩꯂꯣꯟ", "Mtei", ONE_OTHER),
+    lus => ("lus", Mizo, "Mizo ṭawng", "Latn", ONE_OTHER),
+    mn => ("mn", Mongolian, "Монгол", "Cyrl", ONE_OTHER),
+    my => ("my", Myanmar_Burmese, "မြန်မာ", "Mymr", OTHER),
+    ne => ("ne", Nepali, "नेपाली", "Deva", ONE_OTHER),
+    no => ("no", Norwegian, "Norsk", "Latn", ONE_OTHER),
+    or => ("or", Odia_Oriya, "ଓଡ଼ିଆ", "Orya", ONE_OTHER),
+    om => ("om", Oromo, "Afaan Oromoo", "Latn", ONE_OTHER),
+    ps => ("ps", Pashto, "پښتو", "Arab", ONE_OTHER),
+    fa => ("fa", Persian, "فارسی", "Arab", ONE_OTHER),
+    pl => ("pl", Polish, "Polski", "Latn", ONE_FEW_MANY_OTHER),
+    pt => ("pt", Portuguese, "Português", "Latn", ONE_MANY_OTHER),
+    pa => ("pa", Punjabi, "ਪੰਜਾਬੀ", "Guru", ONE_OTHER),
+    qu => ("qu", Quechua, "Runasimi", "Latn", ONE_OTHER),
+    ro => ("ro", Romanian, "Română", "Latn", ONE_FEW_OTHER),
+    ru => ("ru", Russian, "Русский", "Cyrl", ONE_FEW_MANY_OTHER),
+    sm => ("sm", Samoan, "Gagana Sāmoa", "Latn", ONE_OTHER),
+    sa => ("sa", Sanskrit, "संस्कृतम्", "Deva", ONE_OTHER),
+    gd => ("gd", Scots_Gaelic, "Gàidhlig", "Latn", ONE_TWO_FEW_OTHER),
+    nso => ("nso", Sepedi, "Sesotho sa Leboa", "Latn", ONE_OTHER),
+    sr => ("sr", Serbian, "Српски", "Cyrl", ONE_FEW_OTHER),
+    st => ("st", Sesotho, "Sesotho", "Latn", ONE_OTHER),
+    sn => ("sn", Shona, "ChiShona", "Latn", ONE_OTHER),
+    sd => ("sd", Sindhi, "سنڌي", "Arab", ONE_OTHER),
+    si => ("si", Sinhala, "සිංහල", "Sinh", ONE_OTHER),
+    sk => ("sk", Slovak, "Slovenčina", "Latn", ONE_FEW_MANY_OTHER),
+    sl => ("sl", Slovenian, "Slovenščina", "Latn", ONE_TWO_FEW_OTHER),
+    so => ("so", Somali, "Soomaali", "Latn", ONE_OTHER),
+    es => ("es", Spanish, "Español", "Latn", ONE_MANY_OTHER),
+    su => ("su", Sundanese, "Basa Sunda", "Latn", OTHER),
+    sw => ("sw", Swahili, "Kiswahili", "Latn", ONE_OTHER),
+    sv => ("sv", Swedish, "Svenska", "Latn", ONE_OTHER),
+    tg => ("tg", Tajik, "Тоҷикӣ", "Cyrl", ONE_OTHER),
+    ta => ("ta", Tamil, "தமிழ்", "Taml", ONE_OTHER),
+    tt => ("tt", Tatar, "Татарча", "Cyrl", ONE_OTHER),
+    te => ("te", Telugu, "తెలుగు", "Telu", ONE_OTHER),
+    th => ("th", Thai, "ไทย", "Thai", OTHER),
+    ti => ("ti", Tigrinya, "ትግርኛ", "Ethi", ONE_OTHER),
+    ts => ("ts", Tsonga, "Xitsonga", "Latn", ONE_OTHER),
+    tr => ("tr", Turkish, "Türkçe", "Latn", ONE_OTHER),
+    tk => ("tk", Turkmen, "Türkmençe", "Latn", ONE_OTHER),
+    ak => ("ak", Twi, "Twi", "Latn", ONE_OTHER),
+    uk => ("uk", Ukrainian, "Українська", "Cyrl", ONE_FEW_MANY_OTHER),
+    ur => ("ur", Urdu, "اردو", "Arab", ONE_OTHER),
+    ug => ("ug", Uyghur, "ئۇيغۇرچە", "Arab", ONE_OTHER),
+    uz => ("uz", Uzbek, "Oʻzbekcha", "Latn", ONE_OTHER),
+    vi => ("vi", Vietnamese, "Tiếng Việt", "Latn", OTHER),
+    cy => ("cy", Welsh, "Cymraeg", "Latn", ZERO_ONE_TWO_FEW_MANY_OTHER),
+    xh => ("xh", Xhosa, "isiXhosa", "Latn", ONE_OTHER),
+    yi => ("yi", Yiddish, "ייִדיש", "Hebr", ONE_OTHER),
+    yo => ("yo", Yoruba, "Yorùbá", "Latn", OTHER),
+    zu => ("zu", Zulu, "isiZulu", "Latn", ONE_OTHER),
 }
 
 impl LanguageCode {
+    pub fn iter() -> impl Iterator<Item = LanguageCode> {
+        Self::ALL.iter().copied()
+    }
+
+    pub fn source_languages() -> impl Iterator<Item = LanguageCode> {
+        Self::iter()
+    }
+
+    // auto can only be used as a source language
+    pub fn target_languages() -> impl Iterator<Item = LanguageCode> {
+        Self::iter().filter(|lang| !matches!(lang, LanguageCode::auto))
+    }
+
+    // the code most other software expects, google still uses the withdrawn iw and jw
+    pub fn bcp_47(&self) -> &'static str {
+        match self {
+            LanguageCode::iw => "he",
+            LanguageCode::jw => "jv",
+            _ => self.iso_639(),
+        }
+    }
+}
+
+// a LanguageCode other than auto, which can only be a source language
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TargetLanguage(LanguageCode);
+
+impl TargetLanguage {
+    pub fn language_code(&self) -> LanguageCode {
+        self.0
+    }
+
+    pub fn iter() -> impl Iterator<Item = TargetLanguage> {
+        LanguageCode::target_languages().map(TargetLanguage)
+    }
+
     pub fn iso_639(&self) -> &'static str {
+        self.language_code().iso_639()
+    }
+
+    pub fn from_iso_639(s: &str) -> Result<Self, TargetLanguageError> {
+        LanguageCode::from_iso_639(s)
+            .ok_or_else(|| TargetLanguageError::Unknown(s.to_string()))?
+            .try_into()
+    }
+}
+
+impl From<TargetLanguage> for LanguageCode {
+    fn from(target: TargetLanguage) -> Self {
+        target.language_code()
+    }
+}
+
+impl TryFrom<LanguageCode> for TargetLanguage {
+    type Error = TargetLanguageError;
+
+    fn try_from(code: LanguageCode) -> Result<Self, Self::Error> {
+        match code {
+            LanguageCode::auto => Err(TargetLanguageError::Auto),
+            code => Ok(TargetLanguage(code)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TargetLanguageError {
+    Auto,
+    Unknown(String),
+}
+
+impl fmt::Display for TargetLanguageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            LanguageCode::auto => "auto",
-            LanguageCode::af => "af",
-            LanguageCode::sq => "sq",
-            LanguageCode::am => "am",
-            LanguageCode::ar => "ar",
-            LanguageCode::hy => "hy",
-            LanguageCode::r#as => "as",
-            LanguageCode::ay => "ay",
-            LanguageCode::az => "az",
-            LanguageCode::bm => "bm",
-            LanguageCode::eu => "eu",
-            LanguageCode::be => "be",
-            LanguageCode::bn => "bn",
-            LanguageCode::bho => "bho",
-            LanguageCode::bs => "bs",
-            LanguageCode::bg => "bg",
-            LanguageCode::ca => "ca",
-            LanguageCode::ceb => "ceb",
-            LanguageCode::ny => "ny",
-            LanguageCode::zh_CN => "zh-CN",
-            LanguageCode::zh_TW => "zh-TW",
-            LanguageCode::co => "co",
-            LanguageCode::hr => "hr",
-            LanguageCode::cs => "cs",
-            LanguageCode::da => "da",
-            LanguageCode::dv => "dv",
-            LanguageCode::doi => "doi",
-            LanguageCode::nl => "nl",
-            LanguageCode::en => "en",
-            LanguageCode::eo => "eo",
-            LanguageCode::et => "et",
-            LanguageCode::ee => "ee",
-            LanguageCode::tl => "tl",
-            LanguageCode::fi => "fi",
-            LanguageCode::fr => "fr",
-            LanguageCode::fy => "fy",
-            LanguageCode::gl => "gl",
-            LanguageCode::ka => "ka",
-            LanguageCode::de => "de",
-            LanguageCode::el => "el",
-            LanguageCode::gn => "gn",
-            LanguageCode::gu => "gu",
-            LanguageCode::ht => "ht",
-            LanguageCode::ha => "ha",
-            LanguageCode::haw => "haw",
-            LanguageCode::iw => "iw",
-            LanguageCode::hi => "hi",
-            LanguageCode::hmn => "hmn",
-            LanguageCode::hu => "hu",
-            LanguageCode::is => "is",
-            LanguageCode::ig => "ig",
-            LanguageCode::ilo => "ilo",
-            LanguageCode::id => "id",
-            LanguageCode::ga => "ga",
-            LanguageCode::it => "it",
-            LanguageCode::ja => "ja",
-            LanguageCode::jw => "jw",
-            LanguageCode::kn => "kn",
-            LanguageCode::kk => "kk",
-            LanguageCode::km => "km",
-            LanguageCode::rw => "rw",
-            LanguageCode::gom => "gom",
-            LanguageCode::ko => "ko",
-            LanguageCode::kri => "kri",
-            LanguageCode::ku => "ku",
-            LanguageCode::ckb => "ckb",
-            LanguageCode::ky => "ky",
-            LanguageCode::lo => "lo",
-            LanguageCode::la => "la",
-            LanguageCode::lv => "lv",
-            LanguageCode::ln => "ln",
-            LanguageCode::lt => "lt",
-            LanguageCode::lg => "lg",
-            LanguageCode::lb => "lb",
-            LanguageCode::mk => "mk",
-            LanguageCode::mai => "mai",
-            LanguageCode::mg => "mg",
-            LanguageCode::ms => "ms",
-            LanguageCode::ml => "ml",
-            LanguageCode::mt => "mt",
-            LanguageCode::mi => "mi",
-            LanguageCode::mr => "mr",
-            LanguageCode::mni_Mtei => "mni-Mtei",
-            LanguageCode::lus => "lus",
-            LanguageCode::mn => "mn",
-            LanguageCode::my => "my",
-            LanguageCode::ne => "ne",
-            LanguageCode::no => "no",
-            LanguageCode::or => "or",
-            LanguageCode::om => "om",
-            LanguageCode::ps => "ps",
-            LanguageCode::fa => "fa",
-            LanguageCode::pl => "pl",
-            LanguageCode::pt => "pt",
-            LanguageCode::pa => "pa",
-            LanguageCode::qu => "qu",
-            LanguageCode::ro => "ro",
-            LanguageCode::ru => "ru",
-            LanguageCode::sm => "sm",
-            LanguageCode::sa => "sa",
-            LanguageCode::gd => "gd",
-            LanguageCode::nso => "nso",
-            LanguageCode::sr => "sr",
-            LanguageCode::st => "st",
-            LanguageCode::sn => "sn",
-            LanguageCode::sd => "sd",
-            LanguageCode::si => "si",
-            LanguageCode::sk => "sk",
-            LanguageCode::sl => "sl",
-            LanguageCode::so => "so",
-            LanguageCode::es => "es",
-            LanguageCode::su => "su",
-            LanguageCode::sw => "sw",
-            LanguageCode::sv => "sv",
-            LanguageCode::tg => "tg",
-            LanguageCode::ta => "ta",
-            LanguageCode::tt => "tt",
-            LanguageCode::te => "te",
-            LanguageCode::th => "th",
-            LanguageCode::ti => "ti",
-            LanguageCode::ts => "ts",
-            LanguageCode::tr => "tr",
-            LanguageCode::tk => "tk",
-            LanguageCode::ak => "ak",
-            LanguageCode::uk => "uk",
-            LanguageCode::ur => "ur",
-            LanguageCode::ug => "ug",
-            LanguageCode::uz => "uz",
-            LanguageCode::vi => "vi",
-            LanguageCode::cy => "cy",
-            LanguageCode::xh => "xh",
-            LanguageCode::yi => "yi",
-            LanguageCode::yo => "yo",
-            LanguageCode::zu => "zu",
+            TargetLanguageError::Auto => write!(f, "auto can not be used as a target language"),
+            TargetLanguageError::Unknown(code) => write!(f, "unknown language code \"{}\"", code),
         }
     }
+}
+
+impl Error for TargetLanguageError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Ltr,
+    Rtl,
+}
 
-    pub fn from_iso_639(s: &str) -> Option<Self> {
-        match s {
-            "auto" => Some(LanguageCode::auto),
-            "af" => Some(LanguageCode::af),
-            "sq" => Some(LanguageCode::sq),
-            "am" => Some(LanguageCode::am),
-            "ar" => Some(LanguageCode::ar),
-            "hy" => Some(LanguageCode::hy),
-            "as" => Some(LanguageCode::r#as),
-            "ay" => Some(LanguageCode::ay),
-            "az" => Some(LanguageCode::az),
-            "bm" => Some(LanguageCode::bm),
-            "eu" => Some(LanguageCode::eu),
-            "be" => Some(LanguageCode::be),
-            "bn" => Some(LanguageCode::bn),
-            "bho" => Some(LanguageCode::bho),
-            "bs" => Some(LanguageCode::bs),
-            "bg" => Some(LanguageCode::bg),
-            "ca" => Some(LanguageCode::ca),
-            "ceb" => Some(LanguageCode::ceb),
-            "ny" => Some(LanguageCode::ny),
-            "zh-CN" => Some(LanguageCode::zh_CN),
-            "zh-TW" => Some(LanguageCode::zh_TW),
-            "co" => Some(LanguageCode::co),
-            "hr" => Some(LanguageCode::hr),
-            "cs" => Some(LanguageCode::cs),
-            "da" => Some(LanguageCode::da),
-            "dv" => Some(LanguageCode::dv),
-            "doi" => Some(LanguageCode::doi),
-            "nl" => Some(LanguageCode::nl),
-            "en" => Some(LanguageCode::en),
-            "eo" => Some(LanguageCode::eo),
-            "et" => Some(LanguageCode::et),
-            "ee" => Some(LanguageCode::ee),
-            "tl" => Some(LanguageCode::tl),
-            "fi" => Some(LanguageCode::fi),
-            "fr" => Some(LanguageCode::fr),
-            "fy" => Some(LanguageCode::fy),
-            "gl" => Some(LanguageCode::gl),
-            "ka" => Some(LanguageCode::ka),
-            "de" => Some(LanguageCode::de),
-            "el" => Some(LanguageCode::el),
-            "gn" => Some(LanguageCode::gn),
-            "gu" => Some(LanguageCode::gu),
-            "ht" => Some(LanguageCode::ht),
-            "ha" => Some(LanguageCode::ha),
-            "haw" => Some(LanguageCode::haw),
-            "iw" => Some(LanguageCode::iw),
-            "hi" => Some(LanguageCode::hi),
-            "hmn" => Some(LanguageCode::hmn),
-            "hu" => Some(LanguageCode::hu),
-            "is" => Some(LanguageCode::is),
-            "ig" => Some(LanguageCode::ig),
-            "ilo" => Some(LanguageCode::ilo),
-            "id" => Some(LanguageCode::id),
-            "ga" => Some(LanguageCode::ga),
-            "it" => Some(LanguageCode::it),
-            "ja" => Some(LanguageCode::ja),
-            "jw" => Some(LanguageCode::jw),
-            "kn" => Some(LanguageCode::kn),
-            "kk" => Some(LanguageCode::kk),
-            "km" => Some(LanguageCode::km),
-            "rw" => Some(LanguageCode::rw),
-            "gom" => Some(LanguageCode::gom),
-            "ko" => Some(LanguageCode::ko),
-            "kri" => Some(LanguageCode::kri),
-            "ku" => Some(LanguageCode::ku),
-            "ckb" => Some(LanguageCode::ckb),
-            "ky" => Some(LanguageCode::ky),
-            "lo" => Some(LanguageCode::lo),
-            "la" => Some(LanguageCode::la),
-            "lv" => Some(LanguageCode::lv),
-            "ln" => Some(LanguageCode::ln),
-            "lt" => Some(LanguageCode::lt),
-            "lg" => Some(LanguageCode::lg),
-            "lb" => Some(LanguageCode::lb),
-            "mk" => Some(LanguageCode::mk),
-            "mai" => Some(LanguageCode::mai),
-            "mg" => Some(LanguageCode::mg),
-            "ms" => Some(LanguageCode::ms),
-            "ml" => Some(LanguageCode::ml),
-            "mt" => Some(LanguageCode::mt),
-            "mi" => Some(LanguageCode::mi),
-            "mr" => Some(LanguageCode::mr),
-            "mni-Mtei" => Some(LanguageCode::mni_Mtei),
-            "lus" => Some(LanguageCode::lus),
-            "mn" => Some(LanguageCode::mn),
-            "my" => Some(LanguageCode::my),
-            "ne" => Some(LanguageCode::ne),
-            "no" => Some(LanguageCode::no),
-            "or" => Some(LanguageCode::or),
-            "ps" => Some(LanguageCode::ps),
-            "fa" => Some(LanguageCode::fa),
-            "pl" => Some(LanguageCode::pl),
-            "pt" => Some(LanguageCode::pt),
-            "pa" => Some(LanguageCode::pa),
-            "ro" => Some(LanguageCode::ro),
-            "ru" => Some(LanguageCode::ru),
-            "sm" => Some(LanguageCode::sm),
-            "gd" => Some(LanguageCode::gd),
-            "nso" => Some(LanguageCode::nso),
-            "sr" => Some(LanguageCode::sr),
-            "st" => Some(LanguageCode::st),
-            "sn" => Some(LanguageCode::sn),
-            "sd" => Some(LanguageCode::sd),
-            "si" => Some(LanguageCode::si),
-            "sk" => Some(LanguageCode::sk),
-            "sl" => Some(LanguageCode::sl),
-            "so" => Some(LanguageCode::so),
-            "es" => Some(LanguageCode::es),
-            "su" => Some(LanguageCode::su),
-            "sw" => Some(LanguageCode::sw),
-            "sv" => Some(LanguageCode::sv),
-            "tg" => Some(LanguageCode::tg),
-            "ta" => Some(LanguageCode::ta),
-            "tt" => Some(LanguageCode::tt),
-            "te" => Some(LanguageCode::te),
-            "th" => Some(LanguageCode::th),
-            "ti" => Some(LanguageCode::ti),
-            "ts" => Some(LanguageCode::ts),
-            "tr" => Some(LanguageCode::tr),
-            "tk" => Some(LanguageCode::tk),
-            "uk" => Some(LanguageCode::uk),
-            "ur" => Some(LanguageCode::ur),
-            "ug" => Some(LanguageCode::ug),
-            "uz" => Some(LanguageCode::uz),
-            "vi" => Some(LanguageCode::vi),
-            "cy" => Some(LanguageCode::cy),
-            "xh" => Some(LanguageCode::xh),
-            "yi" => Some(LanguageCode::yi),
-            "yo" => Some(LanguageCode::yo),
-            "zu" => Some(LanguageCode::zu),
-            _ => None,
+impl Direction {
+    // Arabic, Hebrew and Thaana are the only right-to-left scripts we support
+    fn from_script(script: &str) -> Self {
+        match script {
+            "Arab" | "Hebr" | "Thaa" => Direction::Rtl,
+            _ => Direction::Ltr,
         }
     }
+}
+
+// CLDR cardinal plural categories, see
+// https://www.unicode.org/cldr/charts/latest/supplemental/language_plural_rules.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PluralCategory {
+    Zero,
+    One,
+    Two,
+    Few,
+    Many,
+    Other,
+}
 
-    pub fn to_language_name(&self) -> &'static LanguageName {
+impl PluralCategory {
+    pub fn as_str(&self) -> &'static str {
         match self {
-            LanguageCode::auto => &LanguageName::Detect_language,
-            LanguageCode::af => &LanguageName::Afrikaans,
-            LanguageCode::sq => &LanguageName::Albanian,
-            LanguageCode::am => &LanguageName::Amharic,
-            LanguageCode::ar => &LanguageName::Arabic,
-            LanguageCode::hy => &LanguageName::Armenian,
-            LanguageCode::r#as => &LanguageName::Assamese,
-            LanguageCode::ay => &LanguageName::Aymara,
-            LanguageCode::az => &LanguageName::Azerbaijani,
-            LanguageCode::bm => &LanguageName::Bambara,
-            LanguageCode::eu => &LanguageName::Basque,
-            LanguageCode::be => &LanguageName::Belarusian,
-            LanguageCode::bn => &LanguageName::Bengali,
-            LanguageCode::bho => &LanguageName::Bhojpuri,
-            LanguageCode::bs => &LanguageName::Bosnian,
-            LanguageCode::bg => &LanguageName::Bulgarian,
-            LanguageCode::ca => &LanguageName::Catalan,
-            LanguageCode::ceb => &LanguageName::Cebuano,
-            LanguageCode::ny => &LanguageName::Chichewa,
-            LanguageCode::zh_CN => &LanguageName::Chinese_Simplified,
-            LanguageCode::zh_TW => &LanguageName::Chinese_Traditional,
-            LanguageCode::co => &LanguageName::Corsican,
-            LanguageCode::hr => &LanguageName::Croatian,
-            LanguageCode::cs => &LanguageName::Czech,
-            LanguageCode::da => &LanguageName::Danish,
-            LanguageCode::dv => &LanguageName::Dhivehi,
-            LanguageCode::doi => &LanguageName::Dogri,
-            LanguageCode::nl => &LanguageName::Dutch,
-            LanguageCode::en => &LanguageName::English,
-            LanguageCode::eo => &LanguageName::Esperanto,
-            LanguageCode::et => &LanguageName::Estonian,
-            LanguageCode::ee => &LanguageName::Ewe,
-            LanguageCode::tl => &LanguageName::Filipino,
-            LanguageCode::fi => &LanguageName::Finnish,
-            LanguageCode::fr => &LanguageName::French,
-            LanguageCode::fy => &LanguageName::Frisian,
-            LanguageCode::gl => &LanguageName::Galician,
-            LanguageCode::ka => &LanguageName::Georgian,
-            LanguageCode::de => &LanguageName::German,
-            LanguageCode::el => &LanguageName::Greek,
-            LanguageCode::gn => &LanguageName::Guarani,
-            LanguageCode::gu => &LanguageName::Gujarati,
-            LanguageCode::ht => &LanguageName::Haitian_Creole,
-            LanguageCode::ha => &LanguageName::Hausa,
-            LanguageCode::haw => &LanguageName::Hawaiian,
-            LanguageCode::iw => &LanguageName::Hebrew,
-            LanguageCode::hi => &LanguageName::Hindi,
-            LanguageCode::hmn => &LanguageName::Hmong,
-            LanguageCode::hu => &LanguageName::Hungarian,
-            LanguageCode::is => &LanguageName::Icelandic,
-            LanguageCode::ig => &LanguageName::Igbo,
-            LanguageCode::ilo => &LanguageName::Ilocano,
-            LanguageCode::id => &LanguageName::Indonesian,
-            LanguageCode::ga => &LanguageName::Irish,
-            LanguageCode::it => &LanguageName::Italian,
-            LanguageCode::ja => &LanguageName::Japanese,
-            LanguageCode::jw => &LanguageName::Javanese,
-            LanguageCode::kn => &LanguageName::Kannada,
-            LanguageCode::kk => &LanguageName::Kazakh,
-            LanguageCode::km => &LanguageName::Khmer,
-            LanguageCode::rw => &LanguageName::Kinyarwanda,
-            LanguageCode::gom => &LanguageName::Konkani,
-            LanguageCode::ko => &LanguageName::Korean,
-            LanguageCode::kri => &LanguageName::Krio,
-            LanguageCode::ku => &LanguageName::Kurdish_Kurmanji,
-            LanguageCode::ckb => &LanguageName::Kurdish_Sorani,
-            LanguageCode::ky => &LanguageName::Kyrgyz,
-            LanguageCode::lo => &LanguageName::Lao,
-            LanguageCode::la => &LanguageName::Latin,
-            LanguageCode::lv => &LanguageName::Latvian,
-            LanguageCode::ln => &LanguageName::Lingala,
-            LanguageCode::lt => &LanguageName::Lithuanian,
-            LanguageCode::lg => &LanguageName::Luganda,
-            LanguageCode::lb => &LanguageName::Luxembourgish,
-            LanguageCode::mk => &LanguageName::Macedonian,
-            LanguageCode::mai => &LanguageName::Maithili,
-            LanguageCode::mg => &LanguageName::Malagasy,
-            LanguageCode::ms => &LanguageName::Malay,
-            LanguageCode::ml => &LanguageName::Malayalam,
-            LanguageCode::mt => &LanguageName::Maltese,
-            LanguageCode::mi => &LanguageName::Maori,
-            LanguageCode::mr => &LanguageName::Marathi,
-            LanguageCode::mni_Mtei => &LanguageName::Meiteilon_Manipuri,
-            LanguageCode::lus => &LanguageName::Mizo,
-            LanguageCode::mn => &LanguageName::Mongolian,
-            LanguageCode::my => &LanguageName::Myanmar_Burmese,
-            LanguageCode::ne => &LanguageName::Nepali,
-            LanguageCode::no => &LanguageName::Norwegian,
-            LanguageCode::or => &LanguageName::Odia_Oriya,
-            LanguageCode::om => &LanguageName::Oromo,
-            LanguageCode::ps => &LanguageName::Pashto,
-            LanguageCode::fa => &LanguageName::Persian,
-            LanguageCode::pl => &LanguageName::Polish,
-            LanguageCode::pt => &LanguageName::Portuguese,
-            LanguageCode::pa => &LanguageName::Punjabi,
-            LanguageCode::qu => &LanguageName::Quechua,
-            LanguageCode::ro => &LanguageName::Romanian,
-            LanguageCode::ru => &LanguageName::Russian,
-            LanguageCode::sm => &LanguageName::Samoan,
-            LanguageCode::sa => &LanguageName::Sanskrit,
-            LanguageCode::gd => &LanguageName::Scots_Gaelic,
-            LanguageCode::nso => &LanguageName::Sepedi,
-            LanguageCode::sr => &LanguageName::Serbian,
-            LanguageCode::st => &LanguageName::Sesotho,
-            LanguageCode::sn => &LanguageName::Shona,
-            LanguageCode::sd => &LanguageName::Sindhi,
-            LanguageCode::si => &LanguageName::Sinhala,
-            LanguageCode::sk => &LanguageName::Slovak,
-            LanguageCode::sl => &LanguageName::Slovenian,
-            LanguageCode::so => &LanguageName::Somali,
-            LanguageCode::es => &LanguageName::Spanish,
-            LanguageCode::su => &LanguageName::Sundanese,
-            LanguageCode::sw => &LanguageName::Swahili,
-            LanguageCode::sv => &LanguageName::Swedish,
-            LanguageCode::tg => &LanguageName::Tajik,
-            LanguageCode::ta => &LanguageName::Tamil,
-            LanguageCode::tt => &LanguageName::Tatar,
-            LanguageCode::te => &LanguageName::Telugu,
-            LanguageCode::th => &LanguageName::Thai,
-            LanguageCode::ti => &LanguageName::Tigrinya,
-            LanguageCode::ts => &LanguageName::Tsonga,
-            LanguageCode::tr => &LanguageName::Turkish,
-            LanguageCode::tk => &LanguageName::Turkmen,
-            LanguageCode::ak => &LanguageName::Twi,
-            LanguageCode::uk => &LanguageName::Ukrainian,
-            LanguageCode::ur => &LanguageName::Urdu,
-            LanguageCode::ug => &LanguageName::Uyghur,
-            LanguageCode::uz => &LanguageName::Uzbek,
-            LanguageCode::vi => &LanguageName::Vietnamese,
-            LanguageCode::cy => &LanguageName::Welsh,
-            LanguageCode::xh => &LanguageName::Xhosa,
-            LanguageCode::yi => &LanguageName::Yiddish,
-            LanguageCode::yo => &LanguageName::Yoruba,
-            LanguageCode::zu => &LanguageName::Zulu,
+            PluralCategory::Zero => "zero",
+            PluralCategory::One => "one",
+            PluralCategory::Two => "two",
+            PluralCategory::Few => "few",
+            PluralCategory::Many => "many",
+            PluralCategory::Other => "other",
         }
     }
 }
 
-#[allow(non_camel_case_types)]
-#[derive(Debug, Default)]
-pub enum LanguageName {
-    #[default]
-    Detect_language,
-    Afrikaans,
-    Albanian,
-    Amharic,
-    Arabic,
-    Armenian,
-    Assamese,
-    Aymara,
-    Azerbaijani,
-    Bambara,
-    Basque,
-    Belarusian,
-    Bengali,
-    Bhojpuri,
-    Bosnian,
-    Bulgarian,
-    Catalan,
-    Cebuano,
-    Chichewa,
-    Chinese_Simplified,
-    Chinese_Traditional,
-    Corsican,
-    Croatian,
-    Czech,
-    Danish,
-    Dhivehi,
-    Dogri,
-    Dutch,
-    English,
-    Esperanto,
-    Estonian,
-    Ewe,
-    Filipino,
-    Finnish,
-    French,
-    Frisian,
-    Galician,
-    Georgian,
-    German,
-    Greek,
-    Guarani,
-    Gujarati,
-    Haitian_Creole,
-    Hausa,
-    Hawaiian,
-    Hebrew,
-    Hindi,
-    Hmong,
-    Hungarian,
-    Icelandic,
-    Igbo,
-    Ilocano,
-    Indonesian,
-    Irish,
-    Italian,
-    Japanese,
-    Javanese,
-    Kannada,
-    Kazakh,
-    Khmer,
-    Kinyarwanda,
-    Konkani,
-    Korean,
-    Krio,
-    Kurdish_Kurmanji,
-    Kurdish_Sorani,
-    Kyrgyz,
-    Lao,
-    Latin,
-    Latvian,
-    Lingala,
-    Lithuanian,
-    Luganda,
-    Luxembourgish,
-    Macedonian,
-    Maithili,
-    Malagasy,
-    Malay,
-    Malayalam,
-    Maltese,
-    Maori,
-    Marathi,
-    Meiteilon_Manipuri,
-    Mizo,
-    Mongolian,
-    Myanmar_Burmese,
-    Nepali,
-    Norwegian,
-    Odia_Oriya,
-    Oromo,
-    Pashto,
-    Persian,
-    Polish,
-    Portuguese,
-    Punjabi,
-    Quechua,
-    Romanian,
-    Russian,
-    Samoan,
-    Sanskrit,
-    Scots_Gaelic,
-    Sepedi,
-    Serbian,
-    Sesotho,
-    Shona,
-    Sindhi,
-    Sinhala,
-    Slovak,
-    Slovenian,
-    Somali,
-    Spanish,
-    Sundanese,
-    Swahili,
-    Swedish,
-    Tajik,
-    Tamil,
-    Tatar,
-    Telugu,
-    Thai,
-    Tigrinya,
-    Tsonga,
-    Turkish,
-    Turkmen,
-    Twi,
-    Ukrainian,
-    Urdu,
-    Uyghur,
-    Uzbek,
-    Vietnamese,
-    Welsh,
-    Xhosa,
-    Yiddish,
-    Yoruba,
-    Zulu,
+const OTHER: &[PluralCategory] = &[PluralCategory::Other];
+const ONE_OTHER: &[PluralCategory] = &[PluralCategory::One, PluralCategory::Other];
+const ZERO_ONE_OTHER: &[PluralCategory] = &[
+    PluralCategory::Zero,
+    PluralCategory::One,
+    PluralCategory::Other,
+];
+const ONE_TWO_OTHER: &[PluralCategory] = &[
+    PluralCategory::One,
+    PluralCategory::Two,
+    PluralCategory::Other,
+];
+const ONE_FEW_OTHER: &[PluralCategory] = &[
+    PluralCategory::One,
+    PluralCategory::Few,
+    PluralCategory::Other,
+];
+const ONE_MANY_OTHER: &[PluralCategory] = &[
+    PluralCategory::One,
+    PluralCategory::Many,
+    PluralCategory::Other,
+];
+const ONE_TWO_FEW_OTHER: &[PluralCategory] = &[
+    PluralCategory::One,
+    PluralCategory::Two,
+    PluralCategory::Few,
+    PluralCategory::Other,
+];
+const ONE_FEW_MANY_OTHER: &[PluralCategory] = &[
+    PluralCategory::One,
+    PluralCategory::Few,
+    PluralCategory::Many,
+    PluralCategory::Other,
+];
+const ONE_TWO_FEW_MANY_OTHER: &[PluralCategory] = &[
+    PluralCategory::One,
+    PluralCategory::Two,
+    PluralCategory::Few,
+    PluralCategory::Many,
+    PluralCategory::Other,
+];
+const ZERO_ONE_TWO_FEW_MANY_OTHER: &[PluralCategory] = &[
+    PluralCategory::Zero,
+    PluralCategory::One,
+    PluralCategory::Two,
+    PluralCategory::Few,
+    PluralCategory::Many,
+    PluralCategory::Other,
+];
+
+// languages without CLDR plural data fall back to one/other
+#[derive(Debug, Clone, Copy)]
+pub struct LanguageInfo {
+    pub code: LanguageCode,
+    // ISO 15924 script code, "Zzzz" for auto
+    pub script: &'static str,
+    pub direction: Direction,
+    pub plural_categories: &'static [PluralCategory],
+}
+
+impl LanguageInfo {
+    pub fn is_rtl(&self) -> bool {
+        self.direction == Direction::Rtl
+    }
 }
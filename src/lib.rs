@@ -15,71 +15,98 @@
 
 use std::error::Error;
 
-pub use lang::LanguageCode;
-use reqwest::{blocking::Response, header::HeaderMap};
+pub use arb::translate_arb;
+pub use batch::translate_segments;
+pub use cancellation::CancellationToken;
+pub use capabilities::{capabilities, Capabilities, Rpc};
+pub use client::{Client, ClientBuilder, RequestOptions};
+pub use cookies::CookieJar;
+pub use csv::{translate_csv, CsvColumn, CsvOptions};
+pub use error::{BlockPage, RequestError};
+pub use fluent::translate_fluent;
+pub use gettext::{translate_catalog, Catalog};
+pub use headers::HeaderSet;
+#[cfg(feature = "html")]
+pub use html::translate_html;
+pub use lang::{LanguageCode, TargetLanguage};
+#[cfg(feature = "markdown")]
+pub use markdown::translate_markdown;
+pub use mobile::{
+    android_values_folder, apple_locale, apple_lproj_folder, translate_apple_strings,
+    translate_xcstrings,
+};
+#[cfg(feature = "xml")]
+pub use mobile::{translate_android_strings, translate_stringsdict};
+pub use properties::translate_properties;
+pub use proxy::ProxyRotation;
+pub use rate_limit::{RateLimit, RateLimiter};
+pub use retry::RetryPolicy;
 use serde_json::Value;
+pub use session::SessionTokens;
+pub use structured::{translate_json, translate_yaml, KeyFilter};
+pub use subtitle::{translate_subtitles, SubtitleFormat, SubtitleOptions, Subtitles};
+pub use transport::{
+    HttpRequest, HttpResponse, ReqwestTransport, Transport, TransportError, TransportErrorKind,
+};
 use urlencoding::encode;
+#[cfg(feature = "xml")]
+pub use xliff::translate_xliff;
 
+pub mod arb;
+pub mod batch;
+pub mod cancellation;
+pub mod capabilities;
+pub mod client;
+pub mod cookies;
+pub mod csv;
+pub mod error;
+pub mod fluent;
+pub mod gettext;
+pub mod headers;
+#[cfg(feature = "html")]
+pub mod html;
 pub mod lang;
+#[cfg(feature = "markdown")]
+pub mod markdown;
+pub mod mobile;
+pub mod properties;
+pub mod proxy;
+pub mod rate_limit;
+pub mod retry;
+pub mod session;
+pub mod structured;
+pub mod subtitle;
+pub mod transport;
+#[cfg(feature = "xml")]
+pub mod xliff;
 
 const GOOGLETTSRPC: &str = "MkEWBc";
+// longest text google accepts in a single request, in bytes
+pub(crate) const MAX_TEXT_LENGTH: usize = 5000;
 const GOOGLETRANSLATEURL: &str =
     "https://translate.google.com/_/TranslateWebserverUi/data/batchexecute";
 
 // https://kovatch.medium.com/deciphering-google-batchexecute-74991e4e446c
-fn package_rpc(
+pub(crate) fn package_rpc(
     text: &str,
     source_language: LanguageCode,
-    target_language: LanguageCode,
+    target_language: TargetLanguage,
 ) -> Vec<u8> {
-    format!(
-        "f.req={}&",
-        encode(
-            format!(
-                "[[[\"{}\",\"[[\\\"{}\\\",\\\"{}\\\",\\\"{}\\\",true],[1]]\",null,\"generic\"]]]",
-                GOOGLETTSRPC,
-                text,
-                source_language.iso_639(),
-                target_language.iso_639(),
-            )
-            .as_str(),
-        )
-    )
-    .into_bytes()
+    // the rpc arguments are a json document embedded as a string, serde_json escapes both levels
+    let arguments = serde_json::json!([
+        [
+            text,
+            source_language.iso_639(),
+            target_language.iso_639(),
+            true
+        ],
+        [1]
+    ]);
+    let rpc = serde_json::json!([[[GOOGLETTSRPC, arguments.to_string(), null, "generic"]]]);
+    format!("f.req={}&", encode(rpc.to_string().as_str())).into_bytes()
 }
 
-fn web_request(bytes: Vec<u8>) -> Result<Response, Box<dyn Error>> {
-    let client = reqwest::blocking::Client::new();
-    let mut headers: HeaderMap = HeaderMap::new();
-    headers.insert(
-        reqwest::header::REFERER,
-        reqwest::header::HeaderValue::from_static("https://translate.google.com/"),
-    );
-    headers.insert(
-        reqwest::header::USER_AGENT,
-        reqwest::header::HeaderValue::from_static(
-            "Mozilla/5.0 (Windows NT 10.0; WOW64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/47.0.2526.106 Safari/537.36"
-        )
-    );
-    headers.insert(
-        reqwest::header::CONTENT_TYPE,
-        reqwest::header::HeaderValue::from_static(
-            "application/x-www-form-urlencoded;charset=utf-8",
-        ),
-    );
-    headers.insert(
-        reqwest::header::CONTENT_LENGTH,
-        reqwest::header::HeaderValue::from_str(bytes.len().to_string().as_str())?,
-    );
-    let response: Response = client
-        .post(GOOGLETRANSLATEURL)
-        .headers(headers)
-        .body(bytes)
-        .send()?;
-    Result::Ok(response)
-}
-
-fn parse_json(json: &str) -> Result<Vec<String>, Box<dyn Error>> {
+pub(crate) fn parse_json(json: &str) -> Result<Vec<String>, Box<dyn Error>> {
     let mut translations: Vec<String> = vec![];
     let err = "unexpected json structure";
     let outerjson: Value = serde_json::from_str(json)?;
@@ -104,6 +131,31 @@ fn parse_json(json: &str) -> Result<Vec<String>, Box<dyn Error>> {
     Result::Ok(translations)
 }
 
+// the full translation, google splits longer texts into sentences and translates them one by one
+pub(crate) fn parse_translation(json: &str) -> Result<String, Box<dyn Error>> {
+    let err = "unexpected json structure";
+    let outerjson: Value = serde_json::from_str(json)?;
+    let innerjson: Value = serde_json::from_str(
+        outerjson
+            .pointer("/0/2")
+            .and_then(|e| e.as_str())
+            .ok_or(err)?,
+    )?;
+    let sentences = innerjson
+        .pointer("/1/0/0/5")
+        .and_then(|e| e.as_array())
+        .ok_or(err)?;
+    let spacing = innerjson
+        .pointer("/1/0/0/3")
+        .and_then(|e| e.as_bool())
+        .unwrap_or(false);
+    let sentences = sentences
+        .iter()
+        .map(|sentence| sentence.get(0).and_then(|e| e.as_str()).ok_or(err))
+        .collect::<Result<Vec<&str>, _>>()?;
+    Result::Ok(sentences.join(if spacing { " " } else { "" }))
+}
+
 #[allow(dead_code)]
 struct TranslationsResult {
     translation: String,
@@ -113,38 +165,13 @@ struct TranslationsResult {
     detected_target_language: LanguageCode,
 }
 
-fn get_response(
-    text: &str,
-    source_language: LanguageCode,
-    target_language: LanguageCode,
-) -> Result<String, Box<dyn Error>> {
-    if text.is_empty() {
-        return Result::Err("text is empty".into());
-    };
-    if text.len() > 5000 {
-        return Result::Err("text can not be longer than 5000 characters".into());
-    };
-    let bytes = package_rpc(text, source_language, target_language);
-    let response = web_request(bytes)?;
-    let response_text = response.text()?;
-    let json = response_text
-        .split('\n')
-        .last()
-        .ok_or("no last")?
-        .to_string();
-    Result::Ok(json)
-}
-
 // text has to between in the range of [1,5000]
 pub fn translate(
     text: &str,
     source_language: LanguageCode,
-    target_language: LanguageCode,
+    target_language: TargetLanguage,
 ) -> Result<Vec<String>, Box<dyn Error>> {
-    let json = get_response(text, source_language, target_language)?;
-    let translations = parse_json(json.as_str())?;
-
-    Result::Ok(translations)
+    Client::new().translate(text, source_language, target_language)
 }
 
 #[cfg(test)]
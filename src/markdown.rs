@@ -0,0 +1,292 @@
+// Copyright (C) 2022  float3
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{error::Error, ops::Range};
+
+use pulldown_cmark::{Event, LinkType, Options, Parser, Tag, TagEnd};
+
+use crate::{
+    batch::{mask_placeholders, translate_segments, Masked},
+    client::{Client, RequestOptions},
+    lang::{LanguageCode, TargetLanguage},
+    structured::{yaml_double, yaml_plain, yaml_single},
+};
+
+// front matter values that are translated, every other key keeps its value
+pub const FRONT_MATTER_KEYS: [&str; 6] = [
+    "title",
+    "subtitle",
+    "description",
+    "summary",
+    "excerpt",
+    "caption",
+];
+
+// translates the prose, link texts and image alt texts of a commonmark document with gfm tables
+// each paragraph, heading or table cell is one segment with its inline markup as placeholders
+// code, html, urls, link targets and the front matter keys are left alone
+// the translations are spliced into the original source so everything else keeps its formatting
+pub fn translate_markdown(
+    client: &Client,
+    markdown: &str,
+    source_language: LanguageCode,
+    target_language: TargetLanguage,
+    options: &RequestOptions,
+) -> Result<String, Box<dyn Error>> {
+    let body_start = front_matter_end(markdown);
+    let mut replacements = front_matter_values(&markdown[..body_start]);
+    replacements.extend(prose_runs(markdown, body_start));
+
+    replacements.retain(|replacement| replacement.masked.has_text());
+    let segments: Vec<&str> = replacements
+        .iter()
+        .map(|replacement| replacement.masked.text())
+        .collect();
+    let translations =
+        translate_segments(client, &segments, source_language, target_language, options)?;
+
+    let mut output = String::with_capacity(markdown.len());
+    let mut position = 0;
+    for (replacement, translation) in replacements.iter().zip(translations) {
+        output.push_str(&markdown[position..replacement.range.start]);
+        output.push_str(&replacement.render(&translation));
+        position = replacement.range.end;
+    }
+    output.push_str(&markdown[position..]);
+    Result::Ok(output)
+}
+
+struct Replacement {
+    range: Range<usize>,
+    masked: Masked,
+    kind: Kind,
+}
+
+enum Kind {
+    Prose,
+    TableCell,
+    FrontMatter { quote: Option<char>, toml: bool },
+}
+
+impl Replacement {
+    fn render(&self, translation: &str) -> String {
+        match self.kind {
+            Kind::Prose => self.masked.unmask(translation),
+            Kind::TableCell => self.masked.unmask(&escape_pipes(translation)),
+            _ => self.kind.render(&self.masked.unmask(translation)),
+        }
+    }
+}
+
+impl Kind {
+    fn render(&self, translation: &str) -> String {
+        match self {
+            Kind::Prose | Kind::TableCell => translation.to_string(),
+            Kind::FrontMatter {
+                quote: Some('\''),
+                toml: false,
+            } => yaml_single(translation),
+            // toml basic strings escape like yaml double quoted ones
+            Kind::FrontMatter {
+                quote: Some('"'), ..
+            } => yaml_double(translation),
+            Kind::FrontMatter {
+                quote: Some(quote), ..
+            } => format!("{}{}{}", quote, translation, quote),
+            Kind::FrontMatter { quote: None, .. } => yaml_plain(translation),
+        }
+    }
+}
+
+// byte offset after a leading yaml (---) or toml (+++) front matter block, 0 if there is none
+fn front_matter_end(markdown: &str) -> usize {
+    let fence = if markdown.starts_with("---") {
+        "---"
+    } else if markdown.starts_with("+++") {
+        "+++"
+    } else {
+        return 0;
+    };
+    let mut lines = markdown.split_inclusive('\n');
+    let mut end = match lines.next() {
+        Some(line) if line.trim_end() == fence => line.len(),
+        _ => return 0,
+    };
+    for line in lines {
+        end += line.len();
+        let line = line.trim_end();
+        if line == fence || (fence == "---" && line == "...") {
+            return end;
+        }
+    }
+    0
+}
+
+// top level `key: value` and `key = "value"` lines whose key is one of FRONT_MATTER_KEYS
+fn front_matter_values(front_matter: &str) -> Vec<Replacement> {
+    let toml = front_matter.starts_with("+++");
+    let mut values = Vec::new();
+    let mut offset = 0;
+    for line in front_matter.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let content = line.trim_end();
+        let separator = if toml { '=' } else { ':' };
+        let Some(split) = content.find(separator) else {
+            continue;
+        };
+        let key = content[..split]
+            .trim()
+            .trim_matches(|c| c == '"' || c == '\'');
+        if content.starts_with(char::is_whitespace) || !FRONT_MATTER_KEYS.contains(&key) {
+            continue;
+        }
+        let value = &content[split + 1..];
+        let value_start = line_start + split + 1 + (value.len() - value.trim_start().len());
+        let value = value.trim();
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'');
+        let text = match quote {
+            Some(quote) if value.len() > 1 && value.ends_with(quote) => &value[1..value.len() - 1],
+            Some(_) => continue,
+            // toml only has quoted strings and yaml block scalars, anchors and flow values stay as they are
+            None if toml || value.starts_with(|c: char| "|>[{&*!#".contains(c)) => continue,
+            None => value,
+        };
+        if text.trim().is_empty() {
+            continue;
+        }
+        values.push(Replacement {
+            range: value_start..value_start + value.len(),
+            masked: match quote {
+                Some('\'') if !toml => mask_placeholders(&text.replace("''", "'")),
+                _ => mask_placeholders(text),
+            },
+            kind: Kind::FrontMatter { quote, toml },
+        });
+    }
+    values
+}
+
+// one run per paragraph, heading or table cell, from its first to its last text
+// the markup between the texts, like emphasis, links, inline code and html, becomes a placeholder
+// and a soft line break a space, so the whole block is translated as one sentence
+fn prose_runs(markdown: &str, body_start: usize) -> Vec<Replacement> {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES;
+    let body = &markdown[body_start..];
+    let mut runs: Vec<Replacement> = Vec::new();
+    let mut current: Option<Replacement> = None;
+    let mut soft_break = false;
+    let mut skipped = 0;
+    let mut table_cell = false;
+    for (event, range) in Parser::new_ext(body, options).into_offset_iter() {
+        let range = range.start + body_start..range.end + body_start;
+        match event {
+            Event::Text(_) if skipped == 0 => {
+                let text = &markdown[range.clone()];
+                match current.as_mut() {
+                    Some(run) => {
+                        let gap = &markdown[run.range.end..range.start];
+                        if soft_break && gap.trim().is_empty() {
+                            run.masked.push_text(" ");
+                        } else if soft_break {
+                            // the markers of a block quote or list the line goes on in
+                            let markers = gap.trim_end();
+                            run.masked.push_placeholder(markers);
+                            run.masked.push_text(&gap[markers.len()..]);
+                        } else if !gap.is_empty() {
+                            run.masked.push_placeholder(gap);
+                        }
+                        run.masked
+                            .push_text_masking_placeholders(text, str::to_string);
+                        run.range.end = range.end;
+                    }
+                    None => {
+                        let mut masked = Masked::default();
+                        masked.push_text_masking_placeholders(text, str::to_string);
+                        current = Some(Replacement {
+                            range,
+                            masked,
+                            kind: if table_cell {
+                                Kind::TableCell
+                            } else {
+                                Kind::Prose
+                            },
+                        });
+                    }
+                }
+                soft_break = false;
+                continue;
+            }
+            // inline content stays in the run
+            Event::Text(_)
+            | Event::Code(_)
+            | Event::InlineHtml(_)
+            | Event::HardBreak
+            | Event::FootnoteReference(_)
+            | Event::Start(Tag::Emphasis | Tag::Strong | Tag::Strikethrough | Tag::Image { .. })
+            | Event::End(
+                TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough | TagEnd::Image,
+            ) => continue,
+            Event::SoftBreak => {
+                soft_break = true;
+                continue;
+            }
+            Event::Start(Tag::Link {
+                link_type: LinkType::Autolink | LinkType::Email,
+                ..
+            }) => {
+                skipped += 1;
+                continue;
+            }
+            Event::Start(Tag::Link { .. }) => continue,
+            Event::End(TagEnd::Link) => {
+                if skipped > 0 {
+                    skipped -= 1;
+                }
+                continue;
+            }
+            Event::Start(Tag::CodeBlock(_) | Tag::HtmlBlock | Tag::MetadataBlock(_)) => {
+                skipped += 1
+            }
+            Event::End(TagEnd::CodeBlock | TagEnd::HtmlBlock | TagEnd::MetadataBlock(_)) => {
+                skipped -= 1
+            }
+            Event::Start(Tag::TableCell) => table_cell = true,
+            Event::End(TagEnd::TableCell) => table_cell = false,
+            _ => {}
+        }
+        runs.extend(current.take());
+        soft_break = false;
+    }
+    runs.extend(current);
+    runs
+}
+
+// a pipe ends a table cell unless it is escaped
+fn escape_pipes(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut previous = None;
+    for c in text.chars() {
+        if c == '|' && previous != Some('\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+        previous = Some(c);
+    }
+    escaped
+}
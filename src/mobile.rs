@@ -0,0 +1,768 @@
+// Copyright (C) 2022  float3
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{collections::HashMap, error::Error, ops::Range};
+
+#[cfg(feature = "xml")]
+use quick_xml::{
+    escape::{partial_escape, unescape},
+    events::Event,
+    Reader,
+};
+use serde_json::{json, Map, Value};
+
+use crate::{
+    batch::{mask_placeholders, translate_segments, Masked},
+    client::{Client, RequestOptions},
+    lang::{LanguageCode, TargetLanguage},
+    structured::write_json,
+};
+
+// the resource folder android loads for a language, like values-de, values-iw or values-zh-rCN
+// android still uses the old codes iw, in and ji and needs the b+ form for three letter codes
+pub fn android_values_folder(language: TargetLanguage) -> String {
+    let code = language.iso_639();
+    let qualifier = match language.language_code() {
+        LanguageCode::id => "in".to_string(),
+        LanguageCode::yi => "ji".to_string(),
+        LanguageCode::jw => "jv".to_string(),
+        _ => match code.split_once('-') {
+            Some((language, region)) if region.len() == 2 => format!("{}-r{}", language, region),
+            Some((language, script)) => format!("b+{}+{}", language, script),
+            None if code.len() > 2 => format!("b+{}", code),
+            None => code.to_string(),
+        },
+    };
+    format!("values-{}", qualifier)
+}
+
+// the locale apple uses for a language, like he, nb or zh-Hans
+pub fn apple_locale(language: TargetLanguage) -> &'static str {
+    match language.language_code() {
+        LanguageCode::zh_CN => "zh-Hans",
+        LanguageCode::zh_TW => "zh-Hant",
+        LanguageCode::no => "nb",
+        LanguageCode::tl => "fil",
+        code => code.bcp_47(),
+    }
+}
+
+// the folder an apple bundle loads for a language, like he.lproj or zh-Hans.lproj
+pub fn apple_lproj_folder(language: TargetLanguage) -> String {
+    format!("{}.lproj", apple_locale(language))
+}
+
+// the text of each plural category of the target language, categories the source does not have
+// fall back to its other form, so "one" is used where both languages have it
+fn plural_forms(
+    source: &HashMap<String, String>,
+    target: TargetLanguage,
+) -> Vec<(&'static str, &str)> {
+    let fallback = source
+        .get("other")
+        .or_else(|| source.values().next())
+        .map(String::as_str)
+        .unwrap_or_default();
+    let mut categories: Vec<&'static str> = target
+        .language_code()
+        .info()
+        .plural_categories
+        .iter()
+        .map(|category| category.as_str())
+        .collect();
+    // apple and android both accept zero for every language
+    if source.contains_key("zero") && !categories.contains(&"zero") {
+        categories.insert(0, "zero");
+    }
+    categories
+        .into_iter()
+        .map(|category| {
+            (
+                category,
+                source.get(category).map_or(fallback, String::as_str),
+            )
+        })
+        .collect()
+}
+
+// masks backslash escapes, line breaks and tabs stay placeholders while quotes and unicode
+// escapes become text, unescaped double quotes as android uses them are placeholders too
+fn mask_escaped(raw: &str, masked: &mut Masked, render: &impl Fn(&str) -> String) {
+    let mut text = String::new();
+    let mut chars = raw.char_indices().peekable();
+    while let Some((position, c)) = chars.next() {
+        let placeholder = match c {
+            '"' => Some(&raw[position..position + 1]),
+            '\\' => match chars.peek().map(|(_, next)| *next) {
+                Some('n' | 't' | 'r') => {
+                    chars.next();
+                    Some(&raw[position..position + 2])
+                }
+                Some('u' | 'U') => {
+                    chars.next();
+                    let digits: String = (0..4)
+                        .filter_map(|_| chars.next())
+                        .map(|(_, c)| c)
+                        .collect();
+                    text.extend(
+                        u32::from_str_radix(&digits, 16)
+                            .ok()
+                            .and_then(char::from_u32),
+                    );
+                    None
+                }
+                Some(next) => {
+                    chars.next();
+                    text.push(next);
+                    None
+                }
+                None => None,
+            },
+            _ => {
+                text.push(c);
+                None
+            }
+        };
+        if let Some(placeholder) = placeholder {
+            masked.push_text_masking_placeholders(&std::mem::take(&mut text), render);
+            masked.push_placeholder(placeholder);
+        }
+    }
+    masked.push_text_masking_placeholders(&text, render);
+}
+
+// escapes for android string resources, apostrophes and quotes always and @ and ? at the start
+#[cfg(feature = "xml")]
+fn android_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for (position, c) in text.chars().enumerate() {
+        match c {
+            '\\' | '\'' | '"' => escaped.push('\\'),
+            '@' | '?' if position == 0 => escaped.push('\\'),
+            _ => {}
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// the content of a string resource or plist string with markup and placeholders masked
+#[cfg(feature = "xml")]
+fn mask_xml(content: &str, android: bool) -> Result<Masked, Box<dyn Error>> {
+    let mut masked = Masked::default();
+    let render = |placeholder: &str| partial_escape(placeholder).into_owned();
+    let mut reader = Reader::from_str(content);
+    reader.config_mut().check_end_names = false;
+    loop {
+        let before = reader.buffer_position() as usize;
+        let event = reader.read_event()?;
+        let raw = &content[before..reader.buffer_position() as usize];
+        match event {
+            Event::Eof => break,
+            Event::Text(_) if android => mask_escaped(&unescape(raw)?, &mut masked, &render),
+            Event::Text(_) => masked.push_text_masking_placeholders(&unescape(raw)?, render),
+            // <xliff:g> marks text that must not be translated
+            Event::Start(start) if start.local_name().as_ref() == b"g" => {
+                reader.read_to_end(start.name())?;
+                masked.push_placeholder(&content[before..reader.buffer_position() as usize]);
+            }
+            _ => masked.push_placeholder(raw),
+        }
+    }
+    Result::Ok(masked)
+}
+
+#[cfg(feature = "xml")]
+fn render_xml(masked: &Masked, translation: &str, android: bool) -> String {
+    let translation = if android {
+        android_escape(translation)
+    } else {
+        translation.to_string()
+    };
+    masked.unmask(&partial_escape(translation.as_str()))
+}
+
+// where a translation goes and which text it comes from
+#[cfg(feature = "xml")]
+enum Resource {
+    // the content of a string
+    Text(Range<usize>, Masked),
+    // the range from the first to the last plural form, the whitespace between two forms and
+    // the masked source of each plural category
+    Plurals(Range<usize>, String, Vec<(String, Masked)>),
+    // a resource with translatable="false", left out of the translation
+    Removed(Range<usize>),
+}
+
+// translates an android strings.xml for the values folder of the target language
+// <string>, <string-array> items and <plurals> are translated, the plural items are rewritten
+// for the plural categories of the target, resources with translatable="false" are left out
+// escaped apostrophes and quotes, \n, format arguments like %1$s and <xliff:g> are kept
+#[cfg(feature = "xml")]
+pub fn translate_android_strings(
+    client: &Client,
+    xml: &str,
+    source_language: LanguageCode,
+    target_language: TargetLanguage,
+    options: &RequestOptions,
+) -> Result<String, Box<dyn Error>> {
+    let resources = parse_android(xml)?;
+    translate_resources(
+        client,
+        xml,
+        resources,
+        true,
+        source_language,
+        target_language,
+        options,
+    )
+}
+
+#[cfg(feature = "xml")]
+fn parse_android(xml: &str) -> Result<Vec<Resource>, Box<dyn Error>> {
+    let mut resources = Vec::new();
+    let mut reader = Reader::from_str(xml);
+    let mut depth = 0;
+    // where the line of the next element starts, so removed resources take their line along
+    let mut line_start = 0;
+    loop {
+        let before = reader.buffer_position() as usize;
+        let event = reader.read_event()?;
+        let after = reader.buffer_position() as usize;
+        match &event {
+            Event::Start(start) if depth == 1 => {
+                reader.read_to_end(start.name())?;
+                let end = reader.buffer_position() as usize;
+                let name = start.local_name();
+                if !is_translatable(start)? {
+                    resources.push(Resource::Removed(line_start..end));
+                } else if name.as_ref() == b"string" {
+                    let content = after..content_end(xml, end);
+                    resources.push(Resource::Text(
+                        content.clone(),
+                        mask_xml(&xml[content], true)?,
+                    ));
+                } else if name.as_ref() == b"string-array" {
+                    for item in items(xml, after..content_end(xml, end))? {
+                        resources.push(Resource::Text(
+                            item.content.clone(),
+                            mask_xml(&xml[item.content], true)?,
+                        ));
+                    }
+                } else if name.as_ref() == b"plurals" {
+                    let items = items(xml, after..content_end(xml, end))?;
+                    if let (Some(first), Some(last)) = (items.first(), items.last()) {
+                        let range = first.range.start..last.range.end;
+                        let separator = first.indentation.clone();
+                        let sources = items
+                            .into_iter()
+                            .map(|item| {
+                                Result::Ok((item.quantity, mask_xml(&xml[item.content], true)?))
+                            })
+                            .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+                        resources.push(Resource::Plurals(range, separator, sources));
+                    }
+                }
+            }
+            Event::Empty(start) if depth == 1 && !is_translatable(start)? => {
+                resources.push(Resource::Removed(line_start..after));
+            }
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            Event::Eof => break,
+            _ => {}
+        }
+        line_start = match event {
+            Event::Text(text) if text.iter().all(u8::is_ascii_whitespace) => {
+                before + xml[before..after].rfind('\n').unwrap_or_default()
+            }
+            _ => after,
+        };
+    }
+    Result::Ok(resources)
+}
+
+#[cfg(feature = "xml")]
+fn is_translatable(start: &quick_xml::events::BytesStart) -> Result<bool, Box<dyn Error>> {
+    Result::Ok(
+        start
+            .try_get_attribute("translatable")?
+            .is_none_or(|value| value.value.as_ref() != b"false"),
+    )
+}
+
+// the position of the end tag of an element that ends at `end`
+#[cfg(feature = "xml")]
+fn content_end(xml: &str, end: usize) -> usize {
+    xml[..end].rfind("</").unwrap_or(end)
+}
+
+#[cfg(feature = "xml")]
+struct Item {
+    quantity: String,
+    range: Range<usize>,
+    content: Range<usize>,
+    // the whitespace in front of the item
+    indentation: String,
+}
+
+// the <item> elements of a string-array or plurals
+#[cfg(feature = "xml")]
+fn items(xml: &str, inner: Range<usize>) -> Result<Vec<Item>, Box<dyn Error>> {
+    let mut items = Vec::new();
+    let mut reader = Reader::from_str(&xml[inner.clone()]);
+    let mut indentation = String::new();
+    loop {
+        let before = inner.start + reader.buffer_position() as usize;
+        let event = reader.read_event()?;
+        let after = inner.start + reader.buffer_position() as usize;
+        match &event {
+            Event::Start(start) if start.local_name().as_ref() == b"item" => {
+                reader.read_to_end(start.name())?;
+                let end = inner.start + reader.buffer_position() as usize;
+                let quantity = start
+                    .try_get_attribute("quantity")?
+                    .map(|quantity| quantity.unescape_value().map(|value| value.into_owned()))
+                    .transpose()?
+                    .unwrap_or_default();
+                items.push(Item {
+                    quantity,
+                    range: before..end,
+                    content: after..content_end(xml, end),
+                    indentation: indentation.clone(),
+                });
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        indentation = match event {
+            Event::Text(text) if text.iter().all(u8::is_ascii_whitespace) => {
+                xml[before..after].to_string()
+            }
+            _ => String::new(),
+        };
+    }
+    Result::Ok(items)
+}
+
+// translates the resources of an android or plist file and splices them into the source
+#[cfg(feature = "xml")]
+fn translate_resources(
+    client: &Client,
+    xml: &str,
+    resources: Vec<Resource>,
+    android: bool,
+    source_language: LanguageCode,
+    target_language: TargetLanguage,
+    options: &RequestOptions,
+) -> Result<String, Box<dyn Error>> {
+    let mut segments: Vec<&str> = Vec::new();
+    for resource in &resources {
+        match resource {
+            Resource::Text(_, masked) if masked.has_text() => segments.push(masked.text()),
+            Resource::Plurals(_, _, sources) => segments.extend(
+                sources
+                    .iter()
+                    .filter(|(_, masked)| masked.has_text())
+                    .map(|(_, masked)| masked.text()),
+            ),
+            _ => {}
+        }
+    }
+    let mut translations =
+        translate_segments(client, &segments, source_language, target_language, options)?
+            .into_iter();
+
+    let mut replacements: Vec<(Range<usize>, String)> = Vec::new();
+    for resource in resources {
+        match resource {
+            Resource::Text(range, masked) => {
+                if masked.has_text() {
+                    let translation = translations.next().unwrap_or_default();
+                    replacements.push((range, render_xml(&masked, &translation, android)));
+                }
+            }
+            Resource::Plurals(range, separator, sources) => {
+                let translated: HashMap<String, String> = sources
+                    .iter()
+                    .map(|(category, masked)| {
+                        let translation = match masked.has_text() {
+                            true => translations.next().unwrap_or_default(),
+                            false => masked.text().to_string(),
+                        };
+                        (category.clone(), render_xml(masked, &translation, android))
+                    })
+                    .collect();
+                let forms: Vec<String> = plural_forms(&translated, target_language)
+                    .into_iter()
+                    .map(|(category, text)| {
+                        if android {
+                            format!("<item quantity=\"{}\">{}</item>", category, text)
+                        } else {
+                            format!(
+                                "<key>{}</key>{}<string>{}</string>",
+                                category, separator, text
+                            )
+                        }
+                    })
+                    .collect();
+                replacements.push((range, forms.join(&separator)));
+            }
+            Resource::Removed(range) => replacements.push((range, String::new())),
+        }
+    }
+
+    let mut output = String::with_capacity(xml.len());
+    let mut position = 0;
+    for (range, replacement) in replacements {
+        output.push_str(&xml[position..range.start]);
+        output.push_str(&replacement);
+        position = range.end;
+    }
+    output.push_str(&xml[position..]);
+    Result::Ok(output)
+}
+
+// translates an apple .stringsdict plist, the format keys and every plural rule
+// the plural rules are rewritten for the plural categories of the target
+#[cfg(feature = "xml")]
+pub fn translate_stringsdict(
+    client: &Client,
+    plist: &str,
+    source_language: LanguageCode,
+    target_language: TargetLanguage,
+    options: &RequestOptions,
+) -> Result<String, Box<dyn Error>> {
+    let resources = parse_stringsdict(plist)?;
+    translate_resources(
+        client,
+        plist,
+        resources,
+        false,
+        source_language,
+        target_language,
+        options,
+    )
+}
+
+// a <key> and the <string> following it
+#[cfg(feature = "xml")]
+struct Pair {
+    key: String,
+    // from <key> to </string>
+    range: Range<usize>,
+    content: Range<usize>,
+    // the whitespace between </key> and <string>
+    separator: String,
+}
+
+#[cfg(feature = "xml")]
+fn parse_stringsdict(plist: &str) -> Result<Vec<Resource>, Box<dyn Error>> {
+    const CATEGORIES: [&str; 6] = ["zero", "one", "two", "few", "many", "other"];
+    let mut resources = Vec::new();
+    let mut reader = Reader::from_str(plist);
+    // the pairs of every open dict
+    let mut dicts: Vec<Vec<Pair>> = Vec::new();
+    let mut key: Option<(String, usize, usize)> = None;
+    loop {
+        let before = reader.buffer_position() as usize;
+        let event = reader.read_event()?;
+        let after = reader.buffer_position() as usize;
+        match &event {
+            Event::Start(start) if start.local_name().as_ref() == b"dict" => {
+                dicts.push(Vec::new());
+                key = None;
+            }
+            Event::Start(start) if start.local_name().as_ref() == b"key" => {
+                let text = reader.read_text(start.name())?;
+                key = Some((
+                    unescape(&text)?.into_owned(),
+                    before,
+                    reader.buffer_position() as usize,
+                ));
+            }
+            Event::Start(start) if start.local_name().as_ref() == b"string" => {
+                reader.read_to_end(start.name())?;
+                let end = reader.buffer_position() as usize;
+                if let (Some((key, key_start, key_end)), Some(pairs)) =
+                    (key.take(), dicts.last_mut())
+                {
+                    pairs.push(Pair {
+                        key,
+                        range: key_start..end,
+                        content: after..content_end(plist, end),
+                        separator: plist[key_end..before].to_string(),
+                    });
+                }
+            }
+            Event::End(end) if end.local_name().as_ref() == b"dict" => {
+                let pairs = dicts.pop().unwrap_or_default();
+                let is_plural_rule = pairs.iter().any(|pair| {
+                    pair.key == "NSStringFormatSpecTypeKey"
+                        && &plist[pair.content.clone()] == "NSStringPluralRuleType"
+                });
+                let forms: Vec<&Pair> = pairs
+                    .iter()
+                    .filter(|pair| CATEGORIES.contains(&pair.key.as_str()))
+                    .collect();
+                if let (true, Some(first), Some(last)) =
+                    (is_plural_rule, forms.first(), forms.last())
+                {
+                    let sources = forms
+                        .iter()
+                        .map(|pair| {
+                            Result::Ok((
+                                pair.key.clone(),
+                                mask_xml(&plist[pair.content.clone()], false)?,
+                            ))
+                        })
+                        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+                    resources.push(Resource::Plurals(
+                        first.range.start..last.range.end,
+                        first.separator.clone(),
+                        sources,
+                    ));
+                }
+                for pair in pairs
+                    .iter()
+                    .filter(|pair| pair.key == "NSStringLocalizedFormatKey")
+                {
+                    resources.push(Resource::Text(
+                        pair.content.clone(),
+                        mask_xml(&plist[pair.content.clone()], false)?,
+                    ));
+                }
+            }
+            Event::Start(_) | Event::Empty(_) => key = None,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    resources.sort_by_key(|resource| match resource {
+        Resource::Text(range, _) | Resource::Plurals(range, _, _) | Resource::Removed(range) => {
+            range.start
+        }
+    });
+    Result::Ok(resources)
+}
+
+// translates an apple .strings file, the values of "key" = "value"; pairs are translated while
+// keys, comments and format arguments like %@ or %1$d are kept
+pub fn translate_apple_strings(
+    client: &Client,
+    strings: &str,
+    source_language: LanguageCode,
+    target_language: TargetLanguage,
+    options: &RequestOptions,
+) -> Result<String, Box<dyn Error>> {
+    let identity = |placeholder: &str| placeholder.to_string();
+    let values: Vec<(Range<usize>, Masked)> = parse_strings(strings)?
+        .into_iter()
+        .map(|range| {
+            let mut masked = Masked::default();
+            mask_escaped(&strings[range.clone()], &mut masked, &identity);
+            (range, masked)
+        })
+        .filter(|(_, masked)| masked.has_text())
+        .collect();
+    let segments: Vec<&str> = values.iter().map(|(_, masked)| masked.text()).collect();
+    let translations =
+        translate_segments(client, &segments, source_language, target_language, options)?;
+
+    let mut output = String::with_capacity(strings.len());
+    let mut position = 0;
+    for ((range, masked), translation) in values.iter().zip(translations) {
+        let escaped = translation.replace('\\', "\\\\").replace('"', "\\\"");
+        output.push_str(&strings[position..range.start]);
+        output.push_str(&masked.unmask(&escaped));
+        position = range.end;
+    }
+    output.push_str(&strings[position..]);
+    Result::Ok(output)
+}
+
+// the ranges of the values in a .strings file, between their quotes
+fn parse_strings(strings: &str) -> Result<Vec<Range<usize>>, Box<dyn Error>> {
+    let bytes = strings.as_bytes();
+    let mut values = Vec::new();
+    let mut position = 0;
+    // the key, the equals sign, the value and the semicolon of a pair
+    let mut expected = 0;
+    loop {
+        position = skip_comments(strings, position);
+        let Some(&byte) = bytes.get(position) else {
+            break;
+        };
+        match (expected, byte) {
+            (0 | 2, b'"') => {
+                let start = position + 1;
+                let mut end = start;
+                while end < bytes.len() && bytes[end] != b'"' {
+                    end += if bytes[end] == b'\\' { 2 } else { 1 };
+                }
+                if end >= bytes.len() {
+                    return Result::Err(format!("unterminated string at {}", position).into());
+                }
+                if expected == 2 {
+                    values.push(start..end);
+                }
+                position = end + 1;
+            }
+            // keys may be unquoted identifiers
+            (0, byte) if byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'.' => {
+                while bytes
+                    .get(position)
+                    .is_some_and(|byte| byte.is_ascii_alphanumeric() || b"_.-".contains(byte))
+                {
+                    position += 1;
+                }
+            }
+            (1, b'=') | (3, b';') => position += 1,
+            _ => {
+                return Result::Err(format!("unexpected {:?} at {}", byte as char, position).into())
+            }
+        }
+        expected = (expected + 1) % 4;
+    }
+    Result::Ok(values)
+}
+
+// the position after whitespace and comments
+fn skip_comments(text: &str, mut position: usize) -> usize {
+    loop {
+        let rest = &text[position..];
+        let trimmed = rest.trim_start();
+        position += rest.len() - trimmed.len();
+        if trimmed.starts_with("/*") {
+            position += trimmed.find("*/").map_or(trimmed.len(), |end| end + 2);
+        } else if trimmed.starts_with("//") {
+            position += trimmed.find('\n').unwrap_or(trimmed.len());
+        } else {
+            return position;
+        }
+    }
+}
+
+// translates an xcode string catalog (.xcstrings), every string without a localization for the
+// target gets one marked needs_review, plural variations are created for the target categories
+// and strings with shouldTranslate false are skipped
+pub fn translate_xcstrings(
+    client: &Client,
+    catalog: &str,
+    source_language: LanguageCode,
+    target_language: TargetLanguage,
+    options: &RequestOptions,
+) -> Result<String, Box<dyn Error>> {
+    let mut root: Value = serde_json::from_str(catalog)?;
+    let source_locale = root
+        .get("sourceLanguage")
+        .and_then(Value::as_str)
+        .unwrap_or("en")
+        .to_string();
+    let locale = apple_locale(target_language);
+    let Some(strings) = root.get_mut("strings").and_then(Value::as_object_mut) else {
+        return Result::Ok(catalog.to_string());
+    };
+
+    // the key of every string to translate with the masked source of each plural category,
+    // a string without plural variations has only other
+    let mut sources: Vec<(String, Vec<(String, Masked)>)> = Vec::new();
+    for (key, entry) in strings.iter() {
+        if entry.get("shouldTranslate") == Some(&Value::Bool(false))
+            || entry
+                .pointer(&format!("/localizations/{}", locale))
+                .is_some()
+        {
+            continue;
+        }
+        let source = entry.pointer(&format!("/localizations/{}", source_locale));
+        let forms: Vec<(String, &str)> =
+            match source.and_then(|source| source.pointer("/variations/plural")) {
+                Some(Value::Object(plural)) => plural
+                    .iter()
+                    .filter_map(|(category, form)| {
+                        let value = form.pointer("/stringUnit/value")?.as_str()?;
+                        Some((category.clone(), value))
+                    })
+                    .collect(),
+                _ => {
+                    let value = source
+                        .and_then(|source| source.pointer("/stringUnit/value"))
+                        .and_then(Value::as_str)
+                        .unwrap_or(key);
+                    vec![("other".to_string(), value)]
+                }
+            };
+        let forms: Vec<(String, Masked)> = forms
+            .into_iter()
+            .map(|(category, value)| (category, mask_placeholders(value)))
+            .collect();
+        if forms.iter().any(|(_, masked)| masked.has_text()) {
+            sources.push((key.clone(), forms));
+        }
+    }
+
+    let segments: Vec<&str> = sources
+        .iter()
+        .flat_map(|(_, forms)| forms.iter().map(|(_, masked)| masked))
+        .filter(|masked| masked.has_text())
+        .map(Masked::text)
+        .collect();
+    let mut translations =
+        translate_segments(client, &segments, source_language, target_language, options)?
+            .into_iter();
+
+    let string_unit =
+        |value: String| json!({ "stringUnit": { "state": "needs_review", "value": value } });
+    for (key, forms) in sources {
+        let is_plural = forms.iter().any(|(category, _)| category != "other");
+        let translated: HashMap<String, String> = forms
+            .iter()
+            .map(|(category, masked)| {
+                let translation = match masked.has_text() {
+                    true => translations.next().unwrap_or_default(),
+                    false => masked.text().to_string(),
+                };
+                (category.clone(), masked.unmask(&translation))
+            })
+            .collect();
+        let localization = if is_plural {
+            let plural: Map<String, Value> = plural_forms(&translated, target_language)
+                .into_iter()
+                .map(|(category, text)| (category.to_string(), string_unit(text.to_string())))
+                .collect();
+            json!({ "variations": { "plural": plural } })
+        } else {
+            string_unit(translated.get("other").cloned().unwrap_or_default())
+        };
+        let Some(entry) = strings.get_mut(&key).and_then(Value::as_object_mut) else {
+            continue;
+        };
+        let localizations = entry
+            .entry("localizations")
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Value::Object(localizations) = localizations {
+            localizations.insert(locale.to_string(), localization);
+            // xcode keeps the localizations sorted by locale
+            localizations.sort_keys();
+        }
+    }
+
+    let mut output = write_json(&root, "  ", " : ");
+    if catalog.ends_with('\n') {
+        output.push('\n');
+    }
+    Result::Ok(output)
+}
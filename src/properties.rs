@@ -0,0 +1,242 @@
+// Copyright (C) 2022  float3
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{error::Error, ops::Range};
+
+use crate::{
+    batch::{translate_segments, Masked},
+    client::{Client, RequestOptions},
+    lang::{LanguageCode, TargetLanguage},
+};
+
+// translates the values of a java .properties file, keys, comments and the layout are kept
+// `\uXXXX` escapes are decoded before translating, a file that is plain ascii stays ascii so
+// it still loads as iso 8859-1, values continued over several lines are written on one line
+// messageformat arguments like `{0}` or `{1,number}` are never sent and apostrophes in values
+// with arguments are doubled as messageformat needs them
+pub fn translate_properties(
+    client: &Client,
+    properties: &str,
+    source_language: LanguageCode,
+    target_language: TargetLanguage,
+    options: &RequestOptions,
+) -> Result<String, Box<dyn Error>> {
+    let values: Vec<(Range<usize>, Masked, bool)> = values(properties)
+        .into_iter()
+        .map(|range| {
+            let raw = &properties[range.clone()];
+            let message_format = is_message_format(raw);
+            (range, mask_value(raw, message_format), message_format)
+        })
+        .filter(|(_, masked, _)| masked.has_text())
+        .collect();
+    let segments: Vec<&str> = values.iter().map(|(_, masked, _)| masked.text()).collect();
+    let translations =
+        translate_segments(client, &segments, source_language, target_language, options)?;
+
+    let ascii = properties.is_ascii();
+    let mut output = String::with_capacity(properties.len());
+    let mut position = 0;
+    for ((range, masked, message_format), translation) in values.iter().zip(translations) {
+        output.push_str(&properties[position..range.start]);
+        output.push_str(&masked.unmask(&escape(&translation, ascii, *message_format)));
+        position = range.end;
+    }
+    output.push_str(&properties[position..]);
+    Result::Ok(output)
+}
+
+// the ranges of the values, from after the separator to the end of the logical line
+fn values(properties: &str) -> Vec<Range<usize>> {
+    let bytes = properties.as_bytes();
+    let is_blank = |byte: u8| matches!(byte, b' ' | b'\t' | b'\x0c');
+    let mut values = Vec::new();
+    let mut position = 0;
+    while position < bytes.len() {
+        while position < bytes.len() && is_blank(bytes[position]) {
+            position += 1;
+        }
+        match bytes.get(position) {
+            None => break,
+            Some(b'\r' | b'\n') => {
+                position += 1;
+                continue;
+            }
+            Some(b'#' | b'!') => {
+                position = physical_line_end(bytes, position);
+                continue;
+            }
+            _ => {}
+        }
+
+        // the key ends at the first unescaped separator or blank
+        while position < bytes.len()
+            && !matches!(bytes[position], b'=' | b':' | b'\r' | b'\n')
+            && !is_blank(bytes[position])
+        {
+            position += if bytes[position] == b'\\' { 2 } else { 1 };
+        }
+        position = position.min(bytes.len());
+        while position < bytes.len() && is_blank(bytes[position]) {
+            position += 1;
+        }
+        if matches!(bytes.get(position), Some(b'=' | b':')) {
+            position += 1;
+            while position < bytes.len() && is_blank(bytes[position]) {
+                position += 1;
+            }
+        }
+
+        // lines ending in an odd number of backslashes continue on the next one
+        let start = position;
+        loop {
+            let end = physical_line_end(bytes, position);
+            let backslashes = bytes[position..end]
+                .iter()
+                .rev()
+                .take_while(|byte| **byte == b'\\')
+                .count();
+            position = end;
+            if backslashes % 2 == 0 || end == bytes.len() {
+                break;
+            }
+            position += if bytes[end..].starts_with(b"\r\n") {
+                2
+            } else {
+                1
+            };
+        }
+        values.push(start..position);
+    }
+    values
+}
+
+fn physical_line_end(bytes: &[u8], start: usize) -> usize {
+    bytes[start..]
+        .iter()
+        .position(|byte| matches!(byte, b'\r' | b'\n'))
+        .map_or(bytes.len(), |end| start + end)
+}
+
+// whether a value has messageformat arguments like `{0}`
+fn is_message_format(raw: &str) -> bool {
+    raw.match_indices('{').any(|(start, _)| {
+        raw[start + 1..]
+            .trim_start()
+            .starts_with(|c: char| c.is_ascii_digit())
+    })
+}
+
+// decodes the escapes of a value, escaped line breaks and tabs stay placeholders
+fn mask_value(raw: &str, message_format: bool) -> Masked {
+    let mut masked = Masked::default();
+    let mut text = String::new();
+    let push_text = |masked: &mut Masked, text: &mut String| {
+        let text = std::mem::take(text);
+        let text = match message_format {
+            true => text.replace("''", "'"),
+            false => text,
+        };
+        masked.push_text_masking_placeholders(&text, str::to_string);
+    };
+    let mut chars = raw.char_indices().peekable();
+    while let Some((position, c)) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some((_, 'n' | 't' | 'r' | 'f')) => {
+                push_text(&mut masked, &mut text);
+                masked.push_placeholder(&raw[position..position + 2]);
+            }
+            Some((_, 'u')) => {
+                let unit = |start: usize| {
+                    raw.get(start..start + 4)
+                        .filter(|digits| digits.chars().all(|c| c.is_ascii_hexdigit()))
+                        .and_then(|digits| u16::from_str_radix(digits, 16).ok())
+                };
+                // a surrogate pair is written as two escapes
+                let low = raw
+                    .get(position + 6..)
+                    .filter(|rest| rest.starts_with("\\u"))
+                    .and_then(|_| unit(position + 8));
+                let decoded = match (unit(position + 2), low) {
+                    (Some(high), Some(low)) if (0xd800..0xdc00).contains(&high) => {
+                        char::decode_utf16([high, low])
+                            .next()
+                            .and_then(Result::ok)
+                            .map(|c| (c, 12))
+                    }
+                    (Some(unit), _) => char::decode_utf16([unit])
+                        .next()
+                        .and_then(Result::ok)
+                        .map(|c| (c, 6)),
+                    (None, _) => None,
+                };
+                let end = match decoded {
+                    Some((c, length)) => {
+                        text.push(c);
+                        position + length
+                    }
+                    // a short or broken escape is kept as it is
+                    None => {
+                        let digits = raw[position + 2..]
+                            .chars()
+                            .take(4)
+                            .take_while(char::is_ascii_hexdigit)
+                            .count();
+                        push_text(&mut masked, &mut text);
+                        masked.push_placeholder(&raw[position..position + 2 + digits]);
+                        position + 2 + digits
+                    }
+                };
+                while chars.next_if(|(next, _)| *next < end).is_some() {}
+            }
+            // a line continuation, the blanks the next line starts with are not part of the value
+            Some((_, '\r' | '\n')) => {
+                while chars
+                    .next_if(|(_, c)| matches!(c, '\n' | ' ' | '\t' | '\x0c'))
+                    .is_some()
+                {}
+            }
+            Some((_, c)) => text.push(c),
+            None => {}
+        }
+    }
+    push_text(&mut masked, &mut text);
+    masked
+}
+
+// escapes a translated value, characters outside ascii become `\uXXXX` if `ascii` is set
+fn escape(text: &str, ascii: bool, message_format: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for (position, c) in text.chars().enumerate() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\'' if message_format => escaped.push_str("''"),
+            ' ' if position == 0 => escaped.push_str("\\ "),
+            c if ascii && !c.is_ascii() => {
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    escaped.push_str(&format!("\\u{:04x}", unit));
+                }
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,84 @@
+// Copyright (C) 2022  float3
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use crate::transport::Transport;
+
+// how requests are spread over the configured proxies
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyRotation {
+    // every request uses the next proxy
+    #[default]
+    RoundRobin,
+    // keep using a proxy until a request through it fails
+    OnFailure,
+    // every thread always uses the same proxy
+    StickyPerThread,
+}
+
+// one transport per proxy, since reqwest fixes the proxy when the client is built
+pub(crate) struct ProxyPool {
+    transports: Vec<Arc<dyn Transport>>,
+    rotation: ProxyRotation,
+    next: AtomicUsize,
+}
+
+impl ProxyPool {
+    pub(crate) fn new(transports: Vec<Arc<dyn Transport>>, rotation: ProxyRotation) -> Self {
+        assert!(
+            !transports.is_empty(),
+            "proxy pool needs at least one transport"
+        );
+        ProxyPool {
+            transports,
+            rotation,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn select(&self) -> (usize, &dyn Transport) {
+        let index = match self.rotation {
+            ProxyRotation::RoundRobin => self.next.fetch_add(1, Ordering::Relaxed),
+            ProxyRotation::OnFailure => self.next.load(Ordering::Relaxed),
+            ProxyRotation::StickyPerThread => {
+                let mut hasher = DefaultHasher::new();
+                thread::current().id().hash(&mut hasher);
+                hasher.finish() as usize
+            }
+        } % self.transports.len();
+        (index, self.transports[index].as_ref())
+    }
+
+    pub(crate) fn report_failure(&self, index: usize) {
+        if self.rotation == ProxyRotation::OnFailure {
+            // only advance once when several threads fail on the same proxy
+            let _ = self.next.compare_exchange(
+                index,
+                (index + 1) % self.transports.len(),
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            );
+        }
+    }
+}
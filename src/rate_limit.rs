@@ -0,0 +1,146 @@
+// Copyright (C) 2022  float3
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    error::Error,
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    pub requests_per_second: f64,
+    // requests that may be sent back to back after being idle
+    pub burst: u32,
+    pub characters_per_minute: Option<u32>,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        RateLimit {
+            requests_per_second: 1.0,
+            burst: 5,
+            characters_per_minute: None,
+        }
+    }
+}
+
+// token buckets shared by every clone of a client, the client sleeps out the waits itself so
+// they count against its deadline and can be cancelled
+#[derive(Debug)]
+pub struct RateLimiter {
+    requests: Mutex<TokenBucket>,
+    characters: Option<Mutex<TokenBucket>>,
+}
+
+impl RateLimiter {
+    // fails for rates that are not positive, a bucket that never refills would block forever
+    pub fn new(limit: RateLimit) -> Result<Self, Box<dyn Error>> {
+        if !(limit.requests_per_second.is_finite() && limit.requests_per_second > 0.0) {
+            return Result::Err(
+                format!(
+                    "requests_per_second must be positive, not {}",
+                    limit.requests_per_second
+                )
+                .into(),
+            );
+        }
+        if limit.characters_per_minute == Some(0) {
+            return Result::Err("characters_per_minute must be positive".into());
+        }
+        Result::Ok(RateLimiter {
+            requests: Mutex::new(TokenBucket::new(
+                f64::from(limit.burst.max(1)),
+                limit.requests_per_second,
+            )),
+            characters: limit.characters_per_minute.map(|characters| {
+                Mutex::new(TokenBucket::new(
+                    f64::from(characters),
+                    f64::from(characters) / 60.0,
+                ))
+            }),
+        })
+    }
+
+    // takes a request token or returns how long to wait before asking again
+    pub fn request_wait(&self) -> Option<Duration> {
+        take(&self.requests, 1.0)
+    }
+
+    // takes the character tokens or returns how long to wait before asking again
+    pub fn characters_wait(&self, characters: usize) -> Option<Duration> {
+        self.characters
+            .as_ref()
+            .and_then(|bucket| take(bucket, characters as f64))
+    }
+
+    pub fn acquire_request(&self) {
+        while let Some(wait) = self.request_wait() {
+            thread::sleep(wait);
+        }
+    }
+
+    pub fn acquire_characters(&self, characters: usize) {
+        while let Some(wait) = self.characters_wait(characters) {
+            thread::sleep(wait);
+        }
+    }
+}
+
+fn take(bucket: &Mutex<TokenBucket>, tokens: f64) -> Option<Duration> {
+    bucket
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .take(tokens)
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_second: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(capacity: f64, refill_per_second: f64) -> Self {
+        TokenBucket {
+            capacity,
+            tokens: capacity,
+            refill_per_second,
+            last_refill: Instant::now(),
+        }
+    }
+
+    // takes the tokens or returns how long to wait before trying again
+    fn take(&mut self, tokens: f64) -> Option<Duration> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_second).min(self.capacity);
+        self.last_refill = now;
+        // a single take larger than the bucket could never succeed
+        let tokens = tokens.min(self.capacity);
+        if self.tokens >= tokens {
+            self.tokens -= tokens;
+            return None;
+        }
+        // a tiny rate can need a wait longer than a duration can hold
+        Some(
+            Duration::try_from_secs_f64((tokens - self.tokens) / self.refill_per_second)
+                .unwrap_or(Duration::MAX),
+        )
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright (C) 2022  float3
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+};
+
+use crate::retry::random_unit;
+
+pub(crate) const GOOGLETRANSLATEHOME: &str = "https://translate.google.com/";
+
+// values the web app embeds in WIZ_global_data and sends with every batchexecute call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionTokens {
+    // FdrFJe
+    pub f_sid: String,
+    // cfb2h, the build label of the frontend
+    pub bl: String,
+    // SNlM0e, only present for some sessions
+    pub at: Option<String>,
+}
+
+impl SessionTokens {
+    pub(crate) fn parse(html: &str) -> Option<Self> {
+        Some(SessionTokens {
+            f_sid: wiz_value(html, "FdrFJe")?,
+            bl: wiz_value(html, "cfb2h")?,
+            at: wiz_value(html, "SNlM0e"),
+        })
+    }
+}
+
+fn wiz_value(html: &str, key: &str) -> Option<String> {
+    let pattern = format!("\"{}\":\"", key);
+    let start = html.find(&pattern)? + pattern.len();
+    let end = start + html[start..].find('"')?;
+    Some(html[start..end].to_string())
+}
+
+#[derive(Debug)]
+pub(crate) struct Session {
+    host_language: String,
+    tokens: Mutex<Option<SessionTokens>>,
+    reqid: AtomicU64,
+}
+
+impl Session {
+    pub(crate) fn new(host_language: String) -> Self {
+        Session {
+            host_language,
+            tokens: Mutex::new(None),
+            // the web app starts at a random four digit number
+            reqid: AtomicU64::new(1000 + (random_unit() * 9000.0) as u64),
+        }
+    }
+
+    pub(crate) fn tokens(&self) -> Option<SessionTokens> {
+        self.tokens
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    pub(crate) fn set_tokens(&self, tokens: Option<SessionTokens>) {
+        *self
+            .tokens
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = tokens;
+    }
+
+    // and every following request adds 100000
+    fn next_reqid(&self) -> u64 {
+        self.reqid.fetch_add(100000, Ordering::Relaxed)
+    }
+
+    pub(crate) fn query(
+        &self,
+        rpc_id: &str,
+        tokens: &SessionTokens,
+    ) -> Vec<(&'static str, String)> {
+        vec![
+            ("rpcids", rpc_id.to_string()),
+            ("source-path", "/".to_string()),
+            ("f.sid", tokens.f_sid.clone()),
+            ("bl", tokens.bl.clone()),
+            ("hl", self.host_language.clone()),
+            ("soc-app", "1".to_string()),
+            ("soc-platform", "1".to_string()),
+            ("soc-device", "1".to_string()),
+            ("_reqid", self.next_reqid().to_string()),
+            ("rt", "c".to_string()),
+        ]
+    }
+}
@@ -0,0 +1,795 @@
+// Copyright (C) 2022  float3
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{error::Error, ops::Range};
+
+use serde_json::Value;
+
+use crate::{
+    batch::{mask_placeholders, translate_segments},
+    client::{Client, RequestOptions},
+    lang::{LanguageCode, TargetLanguage},
+};
+
+// selects the string leaves of a json or yaml file by their dotted key path, like
+// `en.users.title`, sequence items use their index as key
+// `*` matches within one key and `**` any number of keys, so `**.title` matches every title
+#[derive(Debug, Clone, Default)]
+pub struct KeyFilter {
+    // an empty list includes every key
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl KeyFilter {
+    pub fn matches(&self, path: &str) -> bool {
+        let path: Vec<&str> = path.split('.').collect();
+        let matches = |pattern: &String| {
+            let pattern: Vec<&str> = pattern.split('.').collect();
+            glob(&pattern, &path)
+        };
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+}
+
+fn glob(pattern: &[&str], path: &[&str]) -> bool {
+    match (pattern.first(), path.first()) {
+        (None, None) => true,
+        (Some(&"**"), _) => {
+            glob(&pattern[1..], path) || (!path.is_empty() && glob(pattern, &path[1..]))
+        }
+        (Some(segment), Some(key)) => wildcard(segment, key) && glob(&pattern[1..], &path[1..]),
+        _ => false,
+    }
+}
+
+fn wildcard(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => {
+            let Some(text) = text.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=text.len())
+                .filter(|start| text.is_char_boundary(*start))
+                .any(|start| wildcard(rest, &text[start..]))
+        }
+    }
+}
+
+// the key of a file that holds all messages of one language, as rails and some vue-i18n files do
+fn is_language_key(key: &str, language: LanguageCode) -> bool {
+    language != LanguageCode::auto
+        && (key == language.iso_639()
+            || key == language.bcp_47()
+            || key == language.bcp_47().replace('-', "_"))
+}
+
+// translates every string leaf of a json document that the filter selects
+// the file is edited in place, so key order, numbers, escapes and formatting stay as they are
+// a single top level key naming the source language is renamed to the target language
+pub fn translate_json(
+    client: &Client,
+    json: &str,
+    source_language: LanguageCode,
+    target_language: TargetLanguage,
+    filter: &KeyFilter,
+    options: &RequestOptions,
+) -> Result<String, Box<dyn Error>> {
+    // serde_json reports invalid documents
+    serde_json::from_str::<Value>(json)?;
+    let mut leaves = Vec::new();
+    let mut root_keys = Vec::new();
+    scan_json(json, 0, &mut Vec::new(), &mut leaves, &mut root_keys)?;
+    let masked: Vec<_> = leaves
+        .iter()
+        .filter(|leaf| filter.matches(&leaf.path))
+        .map(|leaf| (leaf, mask_placeholders(&leaf.text)))
+        .filter(|(_, masked)| masked.has_text())
+        .collect();
+    let segments: Vec<&str> = masked.iter().map(|(_, masked)| masked.text()).collect();
+    let translations =
+        translate_segments(client, &segments, source_language, target_language, options)?;
+
+    let mut replacements: Vec<(Range<usize>, String)> = masked
+        .iter()
+        .zip(translations)
+        .map(|((leaf, masked), translation)| {
+            let translation = Value::String(masked.unmask(&translation));
+            (leaf.range.clone(), translation.to_string())
+        })
+        .collect();
+    if let [root] = root_keys.as_slice() {
+        if is_language_key(&root.text, source_language) {
+            let key = Value::from(target_language.language_code().bcp_47());
+            replacements.push((root.range.clone(), key.to_string()));
+        }
+    }
+    replacements.sort_by_key(|(range, _)| range.start);
+
+    let mut output = String::with_capacity(json.len());
+    let mut position = 0;
+    for (range, replacement) in replacements {
+        output.push_str(&json[position..range.start]);
+        output.push_str(&replacement);
+        position = range.end;
+    }
+    output.push_str(&json[position..]);
+    Result::Ok(output)
+}
+
+// the indentation of a pretty printed json document, two spaces for a compact one
+pub(crate) fn json_indent(json: &str) -> &str {
+    json.lines()
+        .nth(1)
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .filter(|indent| !indent.is_empty())
+        .unwrap_or("  ")
+}
+
+// pretty json with the given indentation and key separator, serde_json only knows two spaces
+pub(crate) fn write_json(value: &Value, indent: &str, colon: &str) -> String {
+    let mut output = String::new();
+    write_value(&mut output, value, indent, colon, 0);
+    output
+}
+
+fn write_value(output: &mut String, value: &Value, indent: &str, colon: &str, depth: usize) {
+    let (open, close, entries): (char, char, Vec<(Option<&String>, &Value)>) = match value {
+        Value::Array(items) if !items.is_empty() => {
+            ('[', ']', items.iter().map(|item| (None, item)).collect())
+        }
+        Value::Object(map) if !map.is_empty() => (
+            '{',
+            '}',
+            map.iter().map(|(key, item)| (Some(key), item)).collect(),
+        ),
+        _ => {
+            output.push_str(&value.to_string());
+            return;
+        }
+    };
+    output.push(open);
+    for (index, (key, item)) in entries.into_iter().enumerate() {
+        if index > 0 {
+            output.push(',');
+        }
+        output.push('\n');
+        output.push_str(&indent.repeat(depth + 1));
+        if let Some(key) = key {
+            output.push_str(&Value::from(key.as_str()).to_string());
+            output.push_str(colon);
+        }
+        write_value(output, item, indent, colon, depth + 1);
+    }
+    output.push('\n');
+    output.push_str(&indent.repeat(depth));
+    output.push(close);
+}
+
+// a string value or key of a json document
+struct JsonString {
+    // the dotted key path
+    path: String,
+    // the string in the source including its quotes
+    range: Range<usize>,
+    text: String,
+}
+
+// walks the value at `position` of a document serde_json accepted and returns where it ends
+// string values go into `leaves`, the keys of the top level object into `root_keys`
+fn scan_json(
+    json: &str,
+    position: usize,
+    path: &mut Vec<String>,
+    leaves: &mut Vec<JsonString>,
+    root_keys: &mut Vec<JsonString>,
+) -> Result<usize, Box<dyn Error>> {
+    let skip_whitespace = |position: usize| json.len() - json[position..].trim_start().len();
+    let mut position = skip_whitespace(position);
+    let (close, is_object) = match json.as_bytes().get(position) {
+        Some(b'"') => {
+            let (text, end) = json_string(json, position)?;
+            leaves.push(JsonString {
+                path: path.join("."),
+                range: position..end,
+                text,
+            });
+            return Result::Ok(end);
+        }
+        Some(b'[') => (b']', false),
+        Some(b'{') => (b'}', true),
+        Some(_) => {
+            let length = json[position..]
+                .find([',', ']', '}', ' ', '\t', '\n', '\r'])
+                .unwrap_or(json.len() - position);
+            return Result::Ok(position + length);
+        }
+        None => return Result::Err("unexpected end of json".into()),
+    };
+    position = skip_whitespace(position + 1);
+    if json.as_bytes().get(position) == Some(&close) {
+        return Result::Ok(position + 1);
+    }
+    for index in 0.. {
+        let key = if is_object {
+            let (key, end) = json_string(json, position)?;
+            if path.is_empty() {
+                root_keys.push(JsonString {
+                    path: String::new(),
+                    range: position..end,
+                    text: key.clone(),
+                });
+            }
+            // past the colon
+            position = skip_whitespace(end) + 1;
+            key
+        } else {
+            index.to_string()
+        };
+        path.push(key);
+        position = skip_whitespace(scan_json(json, position, path, leaves, root_keys)?);
+        path.pop();
+        match json.as_bytes().get(position) {
+            Some(b',') => position = skip_whitespace(position + 1),
+            Some(byte) if *byte == close => return Result::Ok(position + 1),
+            _ => break,
+        }
+    }
+    Result::Err("unexpected json structure".into())
+}
+
+// the text of the json string starting at `start` and where it ends
+fn json_string(json: &str, start: usize) -> Result<(String, usize), Box<dyn Error>> {
+    let mut escaped = false;
+    for (offset, c) in json[start + 1..].char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => {
+                let end = start + offset + 2;
+                return Result::Ok((serde_json::from_str(&json[start..end])?, end));
+            }
+            _ => {}
+        }
+    }
+    Result::Err("unterminated json string".into())
+}
+
+// translates every string scalar of a yaml document that the filter selects
+// the file is edited in place, so comments, anchors, key order and formatting stay as they are
+// a single top level key naming the source language is renamed to the target language
+pub fn translate_yaml(
+    client: &Client,
+    yaml: &str,
+    source_language: LanguageCode,
+    target_language: TargetLanguage,
+    filter: &KeyFilter,
+    options: &RequestOptions,
+) -> Result<String, Box<dyn Error>> {
+    let document = parse_yaml(yaml);
+    let masked: Vec<_> = document
+        .scalars
+        .iter()
+        .filter(|scalar| filter.matches(&scalar.path))
+        .map(|scalar| (scalar, mask_placeholders(&scalar.text)))
+        .filter(|(_, masked)| masked.has_text())
+        .collect();
+    let segments: Vec<&str> = masked.iter().map(|(_, masked)| masked.text()).collect();
+    let translations =
+        translate_segments(client, &segments, source_language, target_language, options)?;
+
+    let mut replacements: Vec<(Range<usize>, String)> = masked
+        .iter()
+        .zip(translations)
+        .map(|((scalar, masked), translation)| {
+            let translation = masked.unmask(&translation);
+            let rendered = match scalar.style {
+                Style::Plain => yaml_plain(&translation),
+                Style::Single => yaml_single(&translation),
+                Style::Double => yaml_double(&translation),
+                Style::Block => translation,
+            };
+            (scalar.range.clone(), rendered)
+        })
+        .collect();
+    if let [(key, range)] = document.root_keys.as_slice() {
+        if is_language_key(key, source_language) {
+            replacements.push((
+                range.clone(),
+                target_language.language_code().bcp_47().to_string(),
+            ));
+        }
+    }
+    replacements.sort_by_key(|(range, _)| range.start);
+
+    let mut output = String::with_capacity(yaml.len());
+    let mut position = 0;
+    for (range, replacement) in replacements {
+        output.push_str(&yaml[position..range.start]);
+        output.push_str(&replacement);
+        position = range.end;
+    }
+    output.push_str(&yaml[position..]);
+    Result::Ok(output)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Style {
+    Plain,
+    Single,
+    Double,
+    // a line or folded paragraph of a `|` or `>` block scalar
+    Block,
+}
+
+struct Scalar {
+    path: String,
+    // the scalar in the source including its quotes
+    range: Range<usize>,
+    text: String,
+    style: Style,
+}
+
+struct Document {
+    scalars: Vec<Scalar>,
+    // unindented keys and where they are
+    root_keys: Vec<(String, Range<usize>)>,
+}
+
+struct Node {
+    // None for the document itself
+    indent: Option<usize>,
+    key: String,
+    is_item: bool,
+    items: usize,
+}
+
+// a line based reader for the block style yaml that locale files use
+// flow collections, anchors, aliases, tags and non string scalars are skipped
+fn parse_yaml(yaml: &str) -> Document {
+    let mut document = Document {
+        scalars: Vec::new(),
+        root_keys: Vec::new(),
+    };
+    let root = || Node {
+        indent: None,
+        key: String::new(),
+        is_item: false,
+        items: 0,
+    };
+    let mut stack = vec![root()];
+    let lines: Vec<(usize, &str)> = yaml
+        .split_inclusive('\n')
+        .scan(0, |offset, line| {
+            let start = *offset;
+            *offset += line.len();
+            Some((start, line.trim_end_matches(['\n', '\r'])))
+        })
+        .collect();
+
+    let mut index = 0;
+    while index < lines.len() {
+        let (offset, line) = lines[index];
+        index += 1;
+        let content = line.trim_start_matches(' ');
+        let mut indent = line.len() - content.len();
+        if content.is_empty() || content.starts_with('#') {
+            continue;
+        }
+        if content.starts_with("---") || content.starts_with("...") {
+            stack = vec![root()];
+            continue;
+        }
+
+        let line_indent = indent;
+        let mut content = content;
+        if content == "-" || content.starts_with("- ") {
+            while stack.last().is_some_and(|node| {
+                node.indent
+                    .is_some_and(|top| top > indent || (top == indent && node.is_item))
+            }) {
+                stack.pop();
+            }
+            let parent = stack.last_mut().expect("the root is never popped");
+            let item = parent.items;
+            parent.items += 1;
+            stack.push(Node {
+                indent: Some(indent),
+                key: item.to_string(),
+                is_item: true,
+                items: 0,
+            });
+            let rest = content[1..].trim_start_matches(' ');
+            indent += content.len() - rest.len();
+            content = rest;
+            if content.is_empty() || content.starts_with('#') {
+                continue;
+            }
+        } else {
+            while stack
+                .last()
+                .is_some_and(|node| node.indent.is_some_and(|top| top >= indent))
+            {
+                stack.pop();
+            }
+        }
+
+        let content_start = offset + line.len() - content.len();
+        let (key, value_offset) = match split_key(content) {
+            Some((key, value_offset)) => (Some(key), value_offset),
+            None => (None, 0),
+        };
+        if let Some(key) = &key {
+            if indent == 0 {
+                let key_start = content_start + content.find(key.as_str()).unwrap_or_default();
+                document
+                    .root_keys
+                    .push((key.clone(), key_start..key_start + key.len()));
+            }
+        }
+        let value = &content[value_offset..];
+        let value_trimmed = value.trim_start();
+        let value_start = content_start + value_offset + (value.len() - value_trimmed.len());
+        let value = value_trimmed;
+        let path = stack
+            .iter()
+            .skip(1)
+            .map(|node| node.key.as_str())
+            .chain(key.as_deref())
+            .collect::<Vec<_>>()
+            .join(".");
+
+        if value.is_empty() || value.starts_with('#') {
+            if let Some(key) = key {
+                stack.push(Node {
+                    indent: Some(indent),
+                    key,
+                    is_item: false,
+                    items: 0,
+                });
+            }
+            continue;
+        }
+        if value.starts_with(['|', '>']) {
+            let block_end = lines[index..]
+                .iter()
+                .position(|(_, line)| {
+                    let content = line.trim_start_matches(' ');
+                    !content.is_empty() && line.len() - content.len() <= indent
+                })
+                .map_or(lines.len(), |position| index + position);
+            block_scalars(
+                &lines[index..block_end],
+                value.starts_with('>'),
+                &path,
+                &mut document.scalars,
+            );
+            index = block_end;
+            continue;
+        }
+        // a flow scalar goes on over the following lines that are indented deeper than its
+        // key, or than the dash of a sequence item
+        let parent_indent = if key.is_some() { indent } else { line_indent };
+        let scalar_end = lines[index..]
+            .iter()
+            .position(|(_, line)| {
+                let content = line.trim_start_matches(' ');
+                !content.is_empty() && line.len() - content.len() <= parent_indent
+            })
+            .map_or(lines.len(), |position| index + position);
+        let raw_end = lines[index..scalar_end]
+            .iter()
+            .rev()
+            .find(|(_, line)| !line.trim().is_empty())
+            .map_or(offset + line.len(), |(offset, line)| offset + line.len());
+        index = scalar_end;
+        // values that can not be read are left as they are
+        if let Some((text, length, style)) = scalar(&yaml[value_start..raw_end]) {
+            document.scalars.push(Scalar {
+                path,
+                range: value_start..value_start + length,
+                text,
+                style,
+            });
+        }
+    }
+    document
+}
+
+// the key of a `key: value` line and the offset of the value
+fn split_key(content: &str) -> Option<(String, usize)> {
+    if let Some(quote) = content.chars().next().filter(|c| *c == '"' || *c == '\'') {
+        let end = content[1..].find(quote)? + 1;
+        let rest = content[end + 1..].trim_start();
+        let offset = content.len() - rest.len();
+        let after = rest.strip_prefix(':')?;
+        if !after.is_empty() && !after.starts_with(' ') {
+            return None;
+        }
+        return Some((content[1..end].to_string(), offset + 1));
+    }
+    let colon = content
+        .match_indices(':')
+        .map(|(position, _)| position)
+        .find(|position| {
+            let after = &content[position + 1..];
+            after.is_empty() || after.starts_with(' ')
+        })?;
+    let key = content[..colon].trim_end();
+    if key.is_empty() || key.starts_with(['[', '{', '#', '&', '*', '!']) {
+        return None;
+    }
+    Some((key.to_string(), colon + 1))
+}
+
+// the text, the length in the source and the style of a scalar value, which may go on over
+// several lines that are folded as yaml does
+fn scalar(value: &str) -> Option<(String, usize, Style)> {
+    if let Some(inner) = value.strip_prefix('"') {
+        return double_quoted(inner).map(|(text, length)| (text, length + 1, Style::Double));
+    }
+    if let Some(inner) = value.strip_prefix('\'') {
+        let mut text = String::new();
+        let mut position = 0;
+        while let Some(c) = inner[position..].chars().next() {
+            match c {
+                '\'' if inner[position + 1..].starts_with('\'') => {
+                    text.push('\'');
+                    position += 2;
+                }
+                '\'' => return Some((text, position + 2, Style::Single)),
+                '\r' | '\n' => {
+                    text.truncate(text.trim_end_matches([' ', '\t']).len());
+                    let (folded, length) = fold(&inner[position..]);
+                    text.push_str(&folded);
+                    position += length;
+                }
+                c => {
+                    text.push(c);
+                    position += c.len_utf8();
+                }
+            }
+        }
+        return None;
+    }
+    if value.starts_with(['[', '{', '&', '*', '!', '%', '@', '`', '|', '>']) {
+        return None;
+    }
+
+    let mut text = String::new();
+    let mut end = 0;
+    for (index, line) in value.split('\n').enumerate() {
+        let start = value[end..].find(line).map_or(end, |offset| end + offset);
+        let content = line.trim();
+        if index > 0 && content.starts_with('#') {
+            break;
+        }
+        let (content, comment) = match content.find(" #") {
+            Some(comment) => (content[..comment].trim_end(), true),
+            None => (content, false),
+        };
+        if index > 0 {
+            // an empty line stands for a line break, any other line break for a space
+            if content.is_empty() {
+                text.push('\n');
+                continue;
+            }
+            if !text.ends_with('\n') {
+                text.push(' ');
+            }
+        }
+        text.push_str(content);
+        end = start + line.find(content).unwrap_or_default() + content.len();
+        if comment {
+            break;
+        }
+    }
+    let text = text.trim_end_matches('\n').replace(" \n", "\n");
+    if is_non_string(&text) {
+        return None;
+    }
+    Some((text, end, Style::Plain))
+}
+
+// the text of a double quoted scalar after its opening quote and the length up to and
+// including the closing quote, None for escapes yaml does not know
+fn double_quoted(inner: &str) -> Option<(String, usize)> {
+    let mut text = String::new();
+    // escaped whitespace before a line break is kept, other trailing whitespace is not
+    let mut kept = 0;
+    let mut position = 0;
+    while let Some(c) = inner[position..].chars().next() {
+        position += c.len_utf8();
+        match c {
+            '"' => return Some((text, position)),
+            '\r' | '\n' => {
+                text.truncate(text.trim_end_matches([' ', '\t']).len().max(kept));
+                let (folded, length) = fold(&inner[position - 1..]);
+                text.push_str(&folded);
+                position += length - 1;
+            }
+            '\\' => {
+                let escape = inner[position..].chars().next()?;
+                position += escape.len_utf8();
+                let decoded = match escape {
+                    '0' => '\0',
+                    'a' => '\x07',
+                    'b' => '\x08',
+                    't' | '\t' => '\t',
+                    'n' => '\n',
+                    'v' => '\x0b',
+                    'f' => '\x0c',
+                    'r' => '\r',
+                    'e' => '\x1b',
+                    ' ' => ' ',
+                    '"' => '"',
+                    '/' => '/',
+                    '\\' => '\\',
+                    'N' => '\u{85}',
+                    '_' => '\u{a0}',
+                    'L' => '\u{2028}',
+                    'P' => '\u{2029}',
+                    'x' | 'u' | 'U' => {
+                        let digits = match escape {
+                            'x' => 2,
+                            'u' => 4,
+                            _ => 8,
+                        };
+                        let hex = inner.get(position..position + digits)?;
+                        position += digits;
+                        char::from_u32(u32::from_str_radix(hex, 16).ok()?)?
+                    }
+                    // an escaped line break joins the lines without a space
+                    '\r' | '\n' => {
+                        let rest = &inner[position - 1..];
+                        let length =
+                            rest.len() - rest.trim_start_matches([' ', '\t', '\r', '\n']).len();
+                        position += length - 1;
+                        continue;
+                    }
+                    _ => return None,
+                };
+                text.push(decoded);
+                kept = text.len();
+            }
+            c => text.push(c),
+        }
+    }
+    None
+}
+
+// a line break inside a flow scalar with the blank lines and indentation after it, it reads
+// as a space or as one line break less than there are, returns that and the length in the source
+fn fold(rest: &str) -> (String, usize) {
+    let length = rest.len() - rest.trim_start_matches([' ', '\t', '\r', '\n']).len();
+    let breaks = rest[..length].matches('\n').count();
+    let folded = match breaks {
+        0 | 1 => " ".to_string(),
+        breaks => "\n".repeat(breaks - 1),
+    };
+    (folded, length)
+}
+
+// booleans, null, numbers and dates that yaml would not read as strings
+fn is_non_string(text: &str) -> bool {
+    let lower = text.to_lowercase();
+    matches!(
+        lower.as_str(),
+        "" | "~" | "null" | "true" | "false" | "yes" | "no" | "on" | "off" | "y" | "n"
+    ) || is_number(&lower.replace('_', ""))
+        || (lower.starts_with(|c: char| c.is_ascii_digit())
+            && lower
+                .chars()
+                .all(|c| c.is_ascii_digit() || "-:.tz+ ".contains(c)))
+}
+
+// the integer and float forms of the yaml core schema, `.inf`, `.nan`, hex and octal included
+fn is_number(text: &str) -> bool {
+    let digits = |text: &str| !text.is_empty() && text.chars().all(|c| c.is_ascii_digit());
+    if let Some(hex) = text.strip_prefix("0x") {
+        return !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit());
+    }
+    if let Some(octal) = text.strip_prefix("0o") {
+        return !octal.is_empty() && octal.chars().all(|c| ('0'..='7').contains(&c));
+    }
+    let unsigned = text.strip_prefix(['-', '+']).unwrap_or(text);
+    if unsigned == ".inf" || text == ".nan" {
+        return true;
+    }
+    let (mantissa, exponent) = match unsigned.split_once('e') {
+        Some((mantissa, exponent)) => (mantissa, Some(exponent)),
+        None => (unsigned, None),
+    };
+    let mantissa = match mantissa.split_once('.') {
+        Some(("", fraction)) => digits(fraction),
+        Some((whole, fraction)) => digits(whole) && (fraction.is_empty() || digits(fraction)),
+        None => digits(mantissa),
+    };
+    mantissa
+        && exponent
+            .is_none_or(|exponent| digits(exponent.strip_prefix(['-', '+']).unwrap_or(exponent)))
+}
+
+// every line of a literal block is one scalar, a folded block is translated paragraph by
+// paragraph and each paragraph is written back as one line
+fn block_scalars(lines: &[(usize, &str)], folded: bool, path: &str, scalars: &mut Vec<Scalar>) {
+    let mut paragraph: Option<Scalar> = None;
+    for (offset, line) in lines {
+        let content = line.trim();
+        if content.is_empty() {
+            scalars.extend(paragraph.take());
+            continue;
+        }
+        let start = offset + line.len() - line.trim_start().len();
+        let range = start..start + content.len();
+        match paragraph.as_mut() {
+            Some(scalar) if folded => {
+                scalar.text.push(' ');
+                scalar.text.push_str(content);
+                scalar.range.end = range.end;
+            }
+            _ => {
+                scalars.extend(paragraph.take());
+                paragraph = Some(Scalar {
+                    path: path.to_string(),
+                    range,
+                    text: content.to_string(),
+                    style: Style::Block,
+                });
+            }
+        }
+    }
+    scalars.extend(paragraph);
+}
+
+// a plain scalar if yaml reads it back as the same string, double quoted otherwise
+pub(crate) fn yaml_plain(text: &str) -> String {
+    let plain = !text.contains(": ")
+        && !text.contains(" #")
+        && !text.ends_with([':', ' '])
+        && !text.starts_with(|c: char| "-?:,[]{}#&*!|>'\"%@` ".contains(c))
+        && !text.contains(|c: char| c == '\\' || c.is_control())
+        && !is_non_string(text);
+    if plain {
+        text.to_string()
+    } else {
+        yaml_double(text)
+    }
+}
+
+pub(crate) fn yaml_single(text: &str) -> String {
+    if text.contains(char::is_control) {
+        return yaml_double(text);
+    }
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+pub(crate) fn yaml_double(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", u32::from(c))),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
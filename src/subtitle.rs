@@ -0,0 +1,615 @@
+// Copyright (C) 2022  float3
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{error::Error, fmt, time::Duration};
+
+use crate::{
+    batch::translate_segments,
+    client::{Client, RequestOptions},
+    lang::{LanguageCode, TargetLanguage},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubtitleFormat {
+    Srt,
+    WebVtt,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    pub id: Option<String>,
+    pub start: Duration,
+    pub end: Duration,
+    // webvtt cue settings after the end time, like "align:start line:0"
+    pub settings: String,
+    pub lines: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Block {
+    Cue(Cue),
+    // webvtt NOTE, STYLE and REGION blocks, written back unchanged
+    Raw(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subtitles {
+    pub format: SubtitleFormat,
+    // the WEBVTT line and the header lines below it, empty for srt
+    pub header: String,
+    pub blocks: Vec<Block>,
+}
+
+impl Subtitles {
+    // the format is webvtt if the file starts with WEBVTT and srt otherwise
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        let text = text.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+        let mut chunks = text
+            .split("\n\n")
+            .map(|chunk| chunk.trim_matches('\n'))
+            .filter(|chunk| !chunk.is_empty());
+        let is_vtt = text.starts_with("WEBVTT");
+        let format = if is_vtt {
+            SubtitleFormat::WebVtt
+        } else {
+            SubtitleFormat::Srt
+        };
+        let header = if is_vtt {
+            chunks.next().unwrap_or_default().to_string()
+        } else {
+            String::new()
+        };
+
+        let mut blocks = Vec::new();
+        for chunk in chunks {
+            let first = chunk.lines().next().unwrap_or_default();
+            if is_vtt
+                && ["NOTE", "STYLE", "REGION"]
+                    .iter()
+                    .any(|keyword| first == *keyword || first.starts_with(&format!("{} ", keyword)))
+            {
+                blocks.push(Block::Raw(chunk.to_string()));
+                continue;
+            }
+            blocks.push(Block::Cue(parse_cue(chunk)?));
+        }
+        Result::Ok(Subtitles {
+            format,
+            header,
+            blocks,
+        })
+    }
+
+    pub fn cues(&self) -> impl Iterator<Item = &Cue> {
+        self.blocks.iter().filter_map(|block| match block {
+            Block::Cue(cue) => Some(cue),
+            Block::Raw(_) => None,
+        })
+    }
+}
+
+impl fmt::Display for Subtitles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut chunks = Vec::new();
+        if self.format == SubtitleFormat::WebVtt {
+            chunks.push(if self.header.is_empty() {
+                "WEBVTT".to_string()
+            } else {
+                self.header.clone()
+            });
+        }
+        for block in &self.blocks {
+            chunks.push(match block {
+                Block::Raw(raw) => raw.clone(),
+                Block::Cue(cue) => {
+                    let mut chunk = String::new();
+                    if let Some(id) = &cue.id {
+                        chunk.push_str(id);
+                        chunk.push('\n');
+                    }
+                    chunk.push_str(&format_timestamp(cue.start, self.format));
+                    chunk.push_str(" --> ");
+                    chunk.push_str(&format_timestamp(cue.end, self.format));
+                    if !cue.settings.is_empty() {
+                        chunk.push(' ');
+                        chunk.push_str(&cue.settings);
+                    }
+                    for line in &cue.lines {
+                        chunk.push('\n');
+                        chunk.push_str(line);
+                    }
+                    chunk
+                }
+            });
+        }
+        writeln!(f, "{}", chunks.join("\n\n"))
+    }
+}
+
+fn parse_cue(chunk: &str) -> Result<Cue, Box<dyn Error>> {
+    let mut lines = chunk.lines();
+    let mut timing = lines.next().unwrap_or_default();
+    let mut id = None;
+    if !timing.contains("-->") {
+        id = Some(timing.to_string());
+        timing = lines.next().unwrap_or_default();
+    }
+    let (start, rest) = timing
+        .split_once("-->")
+        .ok_or_else(|| format!("missing cue timing in {:?}", chunk))?;
+    let rest = rest.trim();
+    let (end, settings) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    Result::Ok(Cue {
+        id,
+        start: parse_timestamp(start.trim())?,
+        end: parse_timestamp(end)?,
+        settings: settings.trim().to_string(),
+        lines: lines.map(str::to_string).collect(),
+    })
+}
+
+// hh:mm:ss,mmm for srt and hh:mm:ss.mmm or mm:ss.mmm for webvtt
+fn parse_timestamp(timestamp: &str) -> Result<Duration, Box<dyn Error>> {
+    let invalid = || format!("invalid timestamp {:?}", timestamp);
+    let (clock, millis) = timestamp.split_once([',', '.']).ok_or_else(invalid)?;
+    let mut seconds = 0;
+    for part in clock.split(':') {
+        seconds = seconds * 60 + part.parse::<u64>().map_err(|_| invalid())?;
+    }
+    let millis = millis.parse::<u64>().map_err(|_| invalid())?;
+    Result::Ok(Duration::from_secs(seconds) + Duration::from_millis(millis))
+}
+
+fn format_timestamp(timestamp: Duration, format: SubtitleFormat) -> String {
+    let seconds = timestamp.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        if format == SubtitleFormat::Srt {
+            ','
+        } else {
+            '.'
+        },
+        timestamp.subsec_millis()
+    )
+}
+
+#[derive(Debug, Clone)]
+pub struct SubtitleOptions {
+    // visible characters per line, tags do not count
+    pub max_line_length: usize,
+    // cues that do not end a sentence are translated together with the following ones
+    pub merge_cues: bool,
+    // cues further apart than this are never merged
+    pub max_merge_gap: Duration,
+    // upper bound for the length of merged text in characters
+    pub max_merge_length: usize,
+}
+
+impl Default for SubtitleOptions {
+    fn default() -> Self {
+        SubtitleOptions {
+            max_line_length: 42,
+            merge_cues: true,
+            max_merge_gap: Duration::from_millis(1500),
+            max_merge_length: 500,
+        }
+    }
+}
+
+// translates the text of every cue, timing, ids, settings and styling tags stay as they are
+// consecutive cues of one sentence are translated together and the translation is split back
+// over them by length, afterwards every cue is wrapped to max_line_length
+pub fn translate_subtitles(
+    client: &Client,
+    subtitles: &Subtitles,
+    source_language: LanguageCode,
+    target_language: TargetLanguage,
+    subtitle_options: &SubtitleOptions,
+    options: &RequestOptions,
+) -> Result<Subtitles, Box<dyn Error>> {
+    let cues: Vec<&Cue> = subtitles.cues().collect();
+    let units: Vec<Unit> = cues.iter().map(|cue| Unit::new(cue)).collect();
+
+    // groups of cue indexes that are translated as one text
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for (index, unit) in units.iter().enumerate() {
+        if let (Some(group), Unit::Plain { .. }) = (groups.last_mut(), unit) {
+            let previous = group[group.len() - 1];
+            let length: usize = group.iter().map(|i| units[*i].plain_length()).sum();
+            if subtitle_options.merge_cues
+                && units[previous].continues_with(unit)
+                && cues[index].start.saturating_sub(cues[previous].end)
+                    <= subtitle_options.max_merge_gap
+                && length + unit.plain_length() <= subtitle_options.max_merge_length
+            {
+                group.push(index);
+                continue;
+            }
+        }
+        groups.push(vec![index]);
+    }
+
+    let mut segments: Vec<String> = Vec::new();
+    for group in &groups {
+        match &units[group[0]] {
+            Unit::Plain { .. } => segments.push(
+                group
+                    .iter()
+                    .map(|index| units[*index].plain_text())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            Unit::Tagged(pieces) => {
+                segments.extend(pieces.iter().filter(|piece| !is_tag(piece)).cloned())
+            }
+            Unit::Dialogue(lines) => {
+                for (_, pieces) in lines {
+                    segments.extend(pieces.iter().filter(|piece| !is_tag(piece)).cloned())
+                }
+            }
+        }
+    }
+    let segment_refs: Vec<&str> = segments.iter().map(String::as_str).collect();
+    let mut translations = translate_segments(
+        client,
+        &segment_refs,
+        source_language,
+        target_language,
+        options,
+    )?
+    .into_iter();
+
+    let mut translated_lines: Vec<Vec<String>> = vec![Vec::new(); cues.len()];
+    for group in &groups {
+        match &units[group[0]] {
+            Unit::Plain { .. } => {
+                let translation = translations.next().unwrap_or_default();
+                let lengths: Vec<usize> = group
+                    .iter()
+                    .map(|index| units[*index].plain_length())
+                    .collect();
+                for (index, text) in group.iter().zip(split_by_lengths(&translation, &lengths)) {
+                    let Unit::Plain { prefix, suffix, .. } = &units[*index] else {
+                        continue;
+                    };
+                    let text = format!("{}{}{}", prefix, text, suffix);
+                    translated_lines[*index] = wrap(&text, subtitle_options.max_line_length);
+                }
+            }
+            Unit::Tagged(pieces) if pieces.iter().all(|piece| is_tag(piece)) => {
+                translated_lines[group[0]] = cues[group[0]].lines.clone();
+            }
+            Unit::Tagged(pieces) => {
+                let text = restore_tags(pieces, &mut translations);
+                translated_lines[group[0]] = wrap(&text, subtitle_options.max_line_length);
+            }
+            Unit::Dialogue(lines) => {
+                // every speaker starts a line of their own, the dash stays on the first one
+                translated_lines[group[0]] = lines
+                    .iter()
+                    .flat_map(|(dash, pieces)| {
+                        let text = format!("{}{}", dash, restore_tags(pieces, &mut translations));
+                        wrap(&text, subtitle_options.max_line_length)
+                    })
+                    .collect();
+            }
+        }
+    }
+
+    let mut translated_lines = translated_lines.into_iter();
+    let blocks = subtitles
+        .blocks
+        .iter()
+        .map(|block| match block {
+            Block::Raw(raw) => Block::Raw(raw.clone()),
+            Block::Cue(cue) => Block::Cue(Cue {
+                lines: translated_lines.next().unwrap_or_default(),
+                ..cue.clone()
+            }),
+        })
+        .collect();
+    Result::Ok(Subtitles {
+        format: subtitles.format,
+        header: subtitles.header.clone(),
+        blocks,
+    })
+}
+
+// the translatable parts of a cue
+enum Unit {
+    // text with styling only around it, like `<i>text</i>` or `{\an8}text`
+    Plain {
+        prefix: String,
+        text: String,
+        suffix: String,
+    },
+    // text with tags inside, every piece between two tags is translated on its own
+    Tagged(Vec<String>),
+    // lines of different speakers, each with its leading dash and pieces as in Tagged
+    Dialogue(Vec<(String, Vec<String>)>),
+}
+
+impl Unit {
+    fn new(cue: &Cue) -> Self {
+        if cue.lines.len() > 1 && cue.lines.iter().all(|line| line.starts_with('-')) {
+            return Unit::Dialogue(
+                cue.lines
+                    .iter()
+                    .map(|line| {
+                        let text = line.trim_start_matches('-').trim_start();
+                        let dash = line[..line.len() - text.len()].to_string();
+                        (dash, split_tags(text))
+                    })
+                    .collect(),
+            );
+        }
+
+        let text = cue.lines.join(" ");
+        let pieces = split_tags(text.trim());
+        let text_pieces = pieces.iter().filter(|piece| !is_tag(piece)).count();
+        let first_text = pieces.iter().position(|piece| !is_tag(piece));
+        let last_text = pieces.iter().rposition(|piece| !is_tag(piece));
+        match (first_text, last_text) {
+            (Some(first), Some(last)) if text_pieces == 1 => Unit::Plain {
+                prefix: pieces[..first].concat(),
+                text: pieces[first].clone(),
+                suffix: pieces[last + 1..].concat(),
+            },
+            _ => Unit::Tagged(pieces),
+        }
+    }
+
+    fn plain_text(&self) -> &str {
+        match self {
+            Unit::Plain { text, .. } => text,
+            _ => "",
+        }
+    }
+
+    fn plain_length(&self) -> usize {
+        self.plain_text().chars().count()
+    }
+
+    // the next cue is part of the same sentence and styled the same way
+    fn continues_with(&self, next: &Unit) -> bool {
+        match (self, next) {
+            (
+                Unit::Plain {
+                    prefix,
+                    text,
+                    suffix,
+                },
+                Unit::Plain {
+                    prefix: next_prefix,
+                    suffix: next_suffix,
+                    ..
+                },
+            ) => {
+                prefix == next_prefix
+                    && suffix == next_suffix
+                    && !text
+                        .trim_end_matches(['"', '\'', ')', '»', '”'])
+                        .ends_with(['.', '?', '!', '…', '。', '？', '！', ':', ';'])
+            }
+            _ => false,
+        }
+    }
+}
+
+// `<...>` html like tags and `{\...}` ssa override tags
+fn is_tag(piece: &str) -> bool {
+    (piece.starts_with('<') && piece.ends_with('>'))
+        || (piece.starts_with("{\\") && piece.ends_with('}'))
+}
+
+fn restore_tags(pieces: &[String], translations: &mut impl Iterator<Item = String>) -> String {
+    pieces
+        .iter()
+        .map(|piece| {
+            if is_tag(piece) {
+                piece.clone()
+            } else {
+                translations.next().unwrap_or_default()
+            }
+        })
+        .collect()
+}
+
+fn split_tags(text: &str) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let start = [rest.find('<'), rest.find("{\\")]
+            .into_iter()
+            .flatten()
+            .min();
+        let Some(start) = start else {
+            pieces.push(rest.to_string());
+            break;
+        };
+        let close = if rest[start..].starts_with('<') {
+            '>'
+        } else {
+            '}'
+        };
+        let Some(end) = rest[start..].find(close).map(|end| start + end + 1) else {
+            pieces.push(rest.to_string());
+            break;
+        };
+        if start > 0 {
+            pieces.push(rest[..start].to_string());
+        }
+        pieces.push(rest[start..end].to_string());
+        rest = &rest[end..];
+    }
+    pieces
+}
+
+// splits a translation into parts in proportion to the given lengths
+// at spaces if the translation has any, otherwise between characters as for chinese or japanese
+fn split_by_lengths(translation: &str, lengths: &[usize]) -> Vec<String> {
+    if lengths.len() < 2 {
+        return vec![translation.to_string()];
+    }
+    let spaced = translation.contains(char::is_whitespace);
+    let units: Vec<String> = if spaced {
+        translation.split_whitespace().map(str::to_string).collect()
+    } else {
+        translation.chars().map(String::from).collect()
+    };
+    let total_length: usize = lengths.iter().sum::<usize>().max(1);
+    let separator = if spaced { " " } else { "" };
+    let translated_length = units.join(separator).chars().count();
+
+    let mut parts = Vec::new();
+    let mut units = units.into_iter().peekable();
+    let mut remaining_units = units.len();
+    let mut consumed = 0;
+    let mut boundary = 0;
+    for (index, length) in lengths.iter().enumerate() {
+        boundary += length;
+        let remaining_parts = lengths.len() - index - 1;
+        let mut part: Vec<String> = Vec::new();
+        while let Some(unit) = units.peek() {
+            let unit_length = unit.chars().count();
+            // every later part should get at least one unit
+            let enough_left = remaining_units > remaining_parts;
+            let start = consumed + usize::from(spaced && consumed > 0);
+            // the middle of the unit lies before this part's share of the translation
+            let fits = (2 * start + unit_length) * total_length < 2 * translated_length * boundary;
+            if remaining_parts > 0 && !(enough_left && (part.is_empty() || fits)) {
+                break;
+            }
+            consumed = start + unit_length;
+            remaining_units -= 1;
+            part.extend(units.next());
+        }
+        parts.push(part.join(separator));
+    }
+    parts
+}
+
+// greedy line wrap that never breaks inside a tag, words longer than a line and text of
+// scripts without spaces like japanese or chinese are broken between characters
+fn wrap(text: &str, max_line_length: usize) -> Vec<String> {
+    let max_line_length = max_line_length.max(1);
+    let visible = |word: &str| -> usize {
+        split_tags(word)
+            .iter()
+            .filter(|piece| !is_tag(piece))
+            .map(|piece| piece.chars().count())
+            .sum()
+    };
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut line_length = 0;
+    for word in words(text) {
+        let length = visible(&word);
+        if length > max_line_length {
+            let mut remaining = match line.is_empty() {
+                true => max_line_length,
+                false => max_line_length.saturating_sub(line_length + 1),
+            };
+            if remaining == 0 {
+                lines.push(std::mem::take(&mut line));
+                line_length = 0;
+                remaining = max_line_length;
+            }
+            for (index, (piece, length)) in split_word(&word, remaining, max_line_length)
+                .into_iter()
+                .enumerate()
+            {
+                if index > 0 {
+                    lines.push(std::mem::take(&mut line));
+                    line_length = 0;
+                } else if !line.is_empty() {
+                    line.push(' ');
+                    line_length += 1;
+                }
+                line.push_str(&piece);
+                line_length += length;
+            }
+            continue;
+        }
+        if !line.is_empty() && line_length + 1 + length > max_line_length {
+            lines.push(std::mem::take(&mut line));
+            line_length = 0;
+        }
+        if !line.is_empty() {
+            line.push(' ');
+            line_length += 1;
+        }
+        line.push_str(&word);
+        line_length += length;
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+// splits a word at character boundaries into pieces with their visible length, the first
+// piece holds up to `first` characters and the others up to `max`, tags are not counted
+fn split_word(word: &str, first: usize, max: usize) -> Vec<(String, usize)> {
+    let mut pieces = vec![(String::new(), 0)];
+    let mut limit = first;
+    for piece in split_tags(word) {
+        if is_tag(&piece) {
+            if let Some((current, _)) = pieces.last_mut() {
+                current.push_str(&piece);
+            }
+            continue;
+        }
+        for c in piece.chars() {
+            if pieces.last().is_some_and(|(_, length)| *length >= limit) {
+                pieces.push((String::new(), 0));
+                limit = max;
+            }
+            if let Some((current, length)) = pieces.last_mut() {
+                current.push(c);
+                *length += 1;
+            }
+        }
+    }
+    pieces
+}
+
+// splits at whitespace outside of tags
+fn words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ => {}
+        }
+        if c.is_whitespace() && !in_tag {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+        } else {
+            word.push(c);
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
@@ -0,0 +1,323 @@
+// Copyright (C) 2022  float3
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{error::Error, ops::Range};
+
+use quick_xml::{
+    escape::{partial_escape, unescape},
+    events::{BytesStart, Event},
+    Reader,
+};
+
+use crate::{
+    batch::{translate_segments, Masked},
+    client::{Client, RequestOptions},
+    lang::{LanguageCode, TargetLanguage},
+};
+
+// xliff 1.2 elements that hold native code instead of text
+const CODE_ELEMENTS: [&[u8]; 5] = [b"ph", b"bpt", b"ept", b"it", b"sub"];
+
+// pre-translates every unit of an xliff 1.2 or 2.0 file that has no target yet
+// inline elements like <g>, <x/>, <ph> and <pc> are kept out of the text sent to google
+// xliff 1.2 targets get state="needs-review-translation", xliff 2.0 has no such state so the
+// segment is marked translated
+// the file is edited in place, units that already have a target are left alone
+pub fn translate_xliff(
+    client: &Client,
+    xliff: &str,
+    source_language: LanguageCode,
+    target_language: TargetLanguage,
+    options: &RequestOptions,
+) -> Result<String, Box<dyn Error>> {
+    let document = parse_xliff(xliff)?;
+    let language = target_language.language_code().bcp_47();
+    let pending: Vec<(&Segment, Masked)> = document
+        .segments
+        .iter()
+        .map(|segment| Result::Ok((segment, mask_inline(&xliff[segment.source.clone()])?)))
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?
+        .into_iter()
+        .filter(|(_, masked)| masked.has_text())
+        .collect();
+    let segments: Vec<&str> = pending.iter().map(|(_, masked)| masked.text()).collect();
+    let translations =
+        translate_segments(client, &segments, source_language, target_language, options)?;
+
+    let mut replacements: Vec<(Range<usize>, String)> = Vec::new();
+    let attribute = if document.version_2 {
+        "trgLang"
+    } else {
+        "target-language"
+    };
+    for &position in &document.target_languages {
+        replacements.push((
+            position..position,
+            format!(" {}=\"{}\"", attribute, language),
+        ));
+    }
+    for ((segment, masked), translation) in pending.iter().zip(translations) {
+        let content = masked.unmask(&partial_escape(translation.as_str()));
+        let target = if document.version_2 {
+            format!("<target>{}</target>", content)
+        } else {
+            format!(
+                "<target state=\"needs-review-translation\">{}</target>",
+                content
+            )
+        };
+        match &segment.target {
+            Some(range) => replacements.push((range.clone(), target)),
+            None => replacements.push((
+                segment.source_end..segment.source_end,
+                format!("{}{}", segment.indentation, target),
+            )),
+        }
+        if let Some(state) = &segment.state {
+            replacements.push(match state {
+                State::Value(range) => (range.clone(), "translated".to_string()),
+                State::Missing(position) => {
+                    (*position..*position, " state=\"translated\"".to_string())
+                }
+            });
+        }
+    }
+    replacements.sort_by_key(|(range, _)| range.start);
+
+    let mut output = String::with_capacity(xliff.len());
+    let mut position = 0;
+    for (range, replacement) in replacements {
+        output.push_str(&xliff[position..range.start]);
+        output.push_str(&replacement);
+        position = range.end;
+    }
+    output.push_str(&xliff[position..]);
+    Result::Ok(output)
+}
+
+struct Document {
+    version_2: bool,
+    // where to add the target language, once for every <file> of xliff 1.2 that does not name it
+    target_languages: Vec<usize>,
+    // only the segments that still need a translation
+    segments: Vec<Segment>,
+}
+
+// a trans-unit of xliff 1.2 or a segment of xliff 2.0
+#[derive(Default)]
+struct Segment {
+    // the content of <source>
+    source: Range<usize>,
+    // right after </source>
+    source_end: usize,
+    // the whitespace in front of <source>, repeated in front of a new <target>
+    indentation: String,
+    // an empty <target> element that is replaced
+    target: Option<Range<usize>>,
+    translated: bool,
+    // the state attribute of an xliff 2.0 segment
+    state: Option<State>,
+}
+
+enum State {
+    Value(Range<usize>),
+    Missing(usize),
+}
+
+fn parse_xliff(xliff: &str) -> Result<Document, Box<dyn Error>> {
+    let mut document = Document {
+        version_2: false,
+        target_languages: Vec::new(),
+        segments: Vec::new(),
+    };
+    let mut reader = Reader::from_str(xliff);
+    // names of the open elements
+    let mut stack: Vec<Vec<u8>> = Vec::new();
+    let mut segment: Option<Segment> = None;
+    let mut skipped_unit = false;
+    let mut whitespace = String::new();
+    let mut source_start = 0;
+    let mut target_start = 0;
+    let mut target_content_start = 0;
+    loop {
+        let before = reader.buffer_position() as usize;
+        let event = reader.read_event()?;
+        let after = reader.buffer_position() as usize;
+        let parent = stack.last().map(Vec::as_slice);
+        match &event {
+            Event::Start(start) | Event::Empty(start) => {
+                let name = start.local_name().as_ref().to_vec();
+                let empty = matches!(event, Event::Empty(_));
+                match name.as_slice() {
+                    b"xliff" => {
+                        document.version_2 = attribute(start, "version")?
+                            .is_some_and(|version| version.starts_with('2'));
+                        if document.version_2 && attribute(start, "trgLang")?.is_none() {
+                            document
+                                .target_languages
+                                .push(tag_end(xliff, before, after));
+                        }
+                    }
+                    b"file"
+                        if !document.version_2
+                            && attribute(start, "target-language")?.is_none() =>
+                    {
+                        document
+                            .target_languages
+                            .push(tag_end(xliff, before, after));
+                    }
+                    b"trans-unit" | b"unit" => {
+                        skipped_unit = attribute(start, "translate")?.as_deref() == Some("no");
+                        if name == b"trans-unit" && !skipped_unit {
+                            segment = Some(Segment::default());
+                        }
+                    }
+                    b"segment" if !skipped_unit => {
+                        let state = match attribute_range(xliff, before, after, "state") {
+                            Some(range) => State::Value(range),
+                            None => State::Missing(tag_end(xliff, before, after)),
+                        };
+                        segment = Some(Segment {
+                            state: Some(state),
+                            ..Default::default()
+                        });
+                    }
+                    b"source" if is_segment(parent) => {
+                        if let Some(segment) = segment.as_mut() {
+                            segment.indentation = whitespace.clone();
+                            if empty {
+                                segment.source = after..after;
+                                segment.source_end = after;
+                            }
+                        }
+                        source_start = after;
+                    }
+                    b"target" if is_segment(parent) => {
+                        target_start = before;
+                        target_content_start = after;
+                        if let (Some(segment), true) = (segment.as_mut(), empty) {
+                            segment.target = Some(before..after);
+                        }
+                    }
+                    _ => {}
+                }
+                if !empty {
+                    stack.push(name);
+                }
+            }
+            Event::End(end) => {
+                stack.pop();
+                let parent = stack.last().map(Vec::as_slice);
+                match end.local_name().as_ref() {
+                    b"source" if is_segment(parent) => {
+                        if let Some(segment) = segment.as_mut() {
+                            segment.source = source_start..before;
+                            segment.source_end = after;
+                        }
+                    }
+                    b"target" if is_segment(parent) => {
+                        if let Some(segment) = segment.as_mut() {
+                            segment.translated =
+                                !xliff[target_content_start..before].trim().is_empty();
+                            segment.target = Some(target_start..after);
+                        }
+                    }
+                    b"trans-unit" | b"segment" => {
+                        if let Some(segment) = segment.take() {
+                            if !segment.translated {
+                                document.segments.push(segment);
+                            }
+                        }
+                    }
+                    b"unit" => skipped_unit = false,
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        whitespace = match event {
+            Event::Text(text) if text.iter().all(u8::is_ascii_whitespace) => {
+                let text = &xliff[before..after];
+                text[text.rfind('\n').unwrap_or_default()..].to_string()
+            }
+            _ => String::new(),
+        };
+    }
+    Result::Ok(document)
+}
+
+// source and target count only directly below a trans-unit or segment, not in alt-trans
+fn is_segment(parent: Option<&[u8]>) -> bool {
+    matches!(parent, Some(b"trans-unit" | b"segment"))
+}
+
+fn attribute(start: &BytesStart, name: &str) -> Result<Option<String>, Box<dyn Error>> {
+    Result::Ok(match start.try_get_attribute(name)? {
+        Some(attribute) => Some(attribute.unescape_value()?.into_owned()),
+        None => None,
+    })
+}
+
+// the position of the `>` or `/>` that closes a start tag
+fn tag_end(xliff: &str, start: usize, end: usize) -> usize {
+    let tag = &xliff[start..end];
+    start + tag.strip_suffix("/>").map_or(tag.len() - 1, str::len)
+}
+
+// the value of an attribute inside the quotes
+fn attribute_range(xliff: &str, start: usize, end: usize, name: &str) -> Option<Range<usize>> {
+    let tag = &xliff[start..end];
+    let mut search = 0;
+    while let Some(found) = tag[search..].find(name) {
+        let position = search + found;
+        search = position + name.len();
+        if !tag[..position].ends_with(char::is_whitespace) {
+            continue;
+        }
+        let rest = tag[search..].trim_start().strip_prefix('=')?.trim_start();
+        let quote = rest.chars().next()?;
+        let value_start = start + (tag.len() - rest.len()) + 1;
+        return Some(value_start..value_start + rest[1..].find(quote)?);
+    }
+    None
+}
+
+// the text of a source with its inline elements masked
+fn mask_inline(content: &str) -> Result<Masked, Box<dyn Error>> {
+    let mut masked = Masked::default();
+    let mut reader = Reader::from_str(content);
+    reader.config_mut().check_end_names = false;
+    loop {
+        let before = reader.buffer_position() as usize;
+        let event = reader.read_event()?;
+        match event {
+            Event::Eof => break,
+            Event::Text(_) => {
+                let raw = &content[before..reader.buffer_position() as usize];
+                masked.push_text_masking_placeholders(&unescape(raw)?, |placeholder| {
+                    partial_escape(placeholder).into_owned()
+                });
+            }
+            Event::Start(start) if CODE_ELEMENTS.contains(&start.local_name().as_ref()) => {
+                // native code is kept together with its element
+                reader.read_to_end(start.name())?;
+                masked.push_placeholder(&content[before..reader.buffer_position() as usize]);
+            }
+            _ => masked.push_placeholder(&content[before..reader.buffer_position() as usize]),
+        }
+    }
+    Result::Ok(masked)
+}